@@ -0,0 +1,299 @@
+use crate::{
+    hotkeys::{HotkeyAction, HotkeyBindings, KeyEvent},
+    skipper::SkipperState,
+};
+use serde::Deserialize;
+
+/// How the run key turns skipping on and off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActivationMode {
+    /// The run key starts, the pause key stops.
+    #[default]
+    RunPause,
+    /// The run key starts and stops, the pause key is not used.
+    Toggle,
+    /// Skipping only while the run key is held down.
+    Hold,
+}
+
+impl ActivationMode {
+    /// What a binding does in this mode, `None` if it does nothing.
+    pub fn describe(self, action: HotkeyAction) -> Option<&'static str> {
+        match (self, action) {
+            (_, HotkeyAction::Exit) => Some("Exit"),
+            (ActivationMode::RunPause, HotkeyAction::Run) => Some("Run"),
+            (ActivationMode::RunPause, HotkeyAction::Pause) => Some("Pause"),
+            (ActivationMode::Toggle, HotkeyAction::Run) => Some("Run/Pause"),
+            (ActivationMode::Hold, HotkeyAction::Run) => Some("Hold to skip"),
+            (_, HotkeyAction::Pause) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Start,
+    Stop,
+    Exit,
+}
+
+/// Turns key events into start and stop commands according to the mode.
+/// Whether skipping is on is read from the `SkipperState` the commands drive.
+#[derive(Clone, Debug)]
+pub struct Activation {
+    mode: ActivationMode,
+    bindings: HotkeyBindings,
+    /// The run key is down. Windows repeats key-down while a key is held,
+    /// which must not flip the toggle over and over.
+    run_held: bool,
+}
+
+impl Activation {
+    pub fn new(mode: ActivationMode, bindings: HotkeyBindings) -> Self {
+        Self {
+            mode,
+            bindings,
+            run_held: false,
+        }
+    }
+
+    /// The command for a key event while the skipper is in `state`.
+    pub fn on_key(&mut self, event: KeyEvent, state: SkipperState) -> Option<Command> {
+        let active = state.is_armed();
+        match event {
+            KeyEvent::Pressed(combo) => {
+                let action = self.bindings.action_for(combo)?;
+                let repeated = action == HotkeyAction::Run && self.run_held;
+                if action == HotkeyAction::Run {
+                    self.run_held = true;
+                }
+
+                match (self.mode, action) {
+                    (_, HotkeyAction::Exit) => Some(Command::Exit),
+                    (ActivationMode::RunPause | ActivationMode::Hold, HotkeyAction::Run) => {
+                        Self::switch(active, true)
+                    }
+                    (ActivationMode::RunPause, HotkeyAction::Pause) => Self::switch(active, false),
+                    (ActivationMode::Toggle, HotkeyAction::Run) if !repeated => {
+                        Self::switch(active, !active)
+                    }
+                    _ => None,
+                }
+            }
+            // Only the key itself counts, letting go of a modifier first is fine
+            KeyEvent::Released(combo) if combo.key == self.bindings.run.key => {
+                self.run_held = false;
+                match self.mode {
+                    ActivationMode::Hold => Self::switch(active, false),
+                    _ => None,
+                }
+            }
+            KeyEvent::Released(_) => None,
+        }
+    }
+
+    fn switch(active: bool, to: bool) -> Option<Command> {
+        if active == to {
+            return None;
+        }
+        Some(if to { Command::Start } else { Command::Stop })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hotkeys::{KeyCombo, Modifiers},
+        skipper::{Input, Skipper, WindowStatus},
+    };
+
+    const F9: u32 = 0x78;
+    const F10: u32 = 0x79;
+    const F11: u32 = 0x7A;
+    const S: u32 = 0x53;
+
+    fn press(key: u32) -> KeyEvent {
+        KeyEvent::Pressed(KeyCombo::new(key))
+    }
+
+    fn release(key: u32) -> KeyEvent {
+        KeyEvent::Released(KeyCombo::new(key))
+    }
+
+    /// Drives a skipper with the commands, as the main loop does.
+    fn apply(skipper: &mut Skipper, command: Option<Command>) {
+        let input = match command {
+            Some(Command::Start) => Input::Start,
+            Some(Command::Stop) => Input::Stop,
+            Some(Command::Exit) => Input::Shutdown,
+            None => return,
+        };
+        skipper.on_input(input);
+    }
+
+    /// Feeds the events in and collects the commands together with whether
+    /// skipping is on after each one.
+    fn run(mode: ActivationMode, events: &[KeyEvent]) -> Vec<(Option<Command>, bool)> {
+        let mut activation = Activation::new(mode, HotkeyBindings::default());
+        let mut skipper = Skipper::default();
+        skipper.on_input(Input::Window(WindowStatus::Active));
+        events
+            .iter()
+            .map(|event| {
+                let command = activation.on_key(*event, skipper.state());
+                apply(&mut skipper, command);
+                (command, skipper.state().is_armed())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn run_pause_needs_both_keys() {
+        assert_eq!(
+            run(
+                ActivationMode::RunPause,
+                &[
+                    press(F9),
+                    release(F9),
+                    press(F9),
+                    press(F10),
+                    press(F10),
+                    press(F11)
+                ]
+            ),
+            [
+                (Some(Command::Start), true),
+                (None, true),
+                (None, true),
+                (Some(Command::Stop), false),
+                (None, false),
+                (Some(Command::Exit), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle_flips_on_each_press() {
+        assert_eq!(
+            run(
+                ActivationMode::Toggle,
+                &[
+                    press(F9),
+                    release(F9),
+                    press(F10),
+                    press(F9),
+                    release(F9),
+                    press(F9)
+                ]
+            ),
+            [
+                (Some(Command::Start), true),
+                (None, true),
+                (None, true),
+                (Some(Command::Stop), false),
+                (None, false),
+                (Some(Command::Start), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn toggle_ignores_key_repeat() {
+        assert_eq!(
+            run(
+                ActivationMode::Toggle,
+                &[press(F9), press(F9), press(F9), release(F9)]
+            ),
+            [
+                (Some(Command::Start), true),
+                (None, true),
+                (None, true),
+                (None, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_skips_only_while_held() {
+        assert_eq!(
+            run(
+                ActivationMode::Hold,
+                &[
+                    press(F9),
+                    press(F9),
+                    release(F10),
+                    release(F9),
+                    press(F10),
+                    press(F9)
+                ]
+            ),
+            [
+                (Some(Command::Start), true),
+                (None, true),
+                (None, true),
+                (Some(Command::Stop), false),
+                (None, false),
+                (Some(Command::Start), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_ends_on_key_release_even_if_modifiers_went_first() {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::NONE
+        };
+        let bindings = HotkeyBindings {
+            run: KeyCombo::with(ctrl, S),
+            ..HotkeyBindings::default()
+        };
+        let mut activation = Activation::new(ActivationMode::Hold, bindings);
+
+        assert_eq!(activation.on_key(press(S), SkipperState::Idle), None);
+        assert_eq!(
+            activation.on_key(
+                KeyEvent::Pressed(KeyCombo::with(ctrl, S)),
+                SkipperState::Idle
+            ),
+            Some(Command::Start)
+        );
+        assert_eq!(
+            activation.on_key(release(S), SkipperState::Skipping),
+            Some(Command::Stop)
+        );
+    }
+
+    #[test]
+    fn reads_whether_skipping_is_on_from_the_skipper() {
+        let mut activation = Activation::new(ActivationMode::Toggle, HotkeyBindings::default());
+        // Armed without a window is on as far as the keys go
+        assert_eq!(
+            activation.on_key(press(F9), SkipperState::ArmedNoWindow),
+            Some(Command::Stop)
+        );
+        assert_eq!(activation.on_key(release(F9), SkipperState::Paused), None);
+        assert_eq!(
+            activation.on_key(press(F9), SkipperState::Paused),
+            Some(Command::Start)
+        );
+    }
+
+    #[test]
+    fn menu_only_lists_keys_the_mode_uses() {
+        let described = |mode: ActivationMode| {
+            HotkeyBindings::default()
+                .iter()
+                .filter_map(|(action, _)| mode.describe(action))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            described(ActivationMode::RunPause),
+            ["Run", "Pause", "Exit"]
+        );
+        assert_eq!(described(ActivationMode::Toggle), ["Run/Pause", "Exit"]);
+        assert_eq!(described(ActivationMode::Hold), ["Hold to skip", "Exit"]);
+    }
+}
@@ -1,35 +1,233 @@
-use color_eyre::{eyre::Context, Report, Result};
+use color_eyre::{eyre::Context, Result};
 
 use std::{
     sync::{
-        mpsc::{channel, Sender, TryRecvError},
-        Arc, Mutex, MutexGuard, PoisonError,
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc,
     },
     thread::JoinHandle,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
 use crate::{
-    genshin::{Window, WindowProps},
+    activation::{Activation, ActivationMode, Command},
+    capture::GdiFrameGrabber,
+    config::Config,
+    dispatch::HOOK_ERRORS,
+    genshin::{DialogAction, Reaction, Window, WindowProps},
     global_states::{EventType, EVENT_LISTENER_CHANNEL},
+    hotkeys::{HotkeyBindings, KeyCombo},
+    options::ChoiceState,
+    pixel_source::FrameBuffer,
+    profile::GameProfile,
+    shutdown,
+    skipper::{Input, Skipper, SkipperState, WindowStatus},
+    strategy::Strategy,
+    supervisor::{self, RestartPolicy},
+    win_events::{Debounce, WIN_EVENTS},
     winapi_bindings::{
-        hooks::{ApplicationShutdown, ChangeForegroundWindow, DestroyWindow, KeyboardEvent},
+        bindings::{get_current_thread_id, post_quit_to_thread, set_process_dpi_aware},
+        hooks::{
+            ApplicationShutdown, ChangeForegroundWindow, DestroyWindow, KeyboardEvent,
+            LocationChange,
+        },
         message_manager::MessageManager,
     },
+    worker::{self, TargetSlot},
 };
 
 #[derive(Error, Debug)]
 enum AppErr {
-    #[error("Error when locking mutex: {0}")]
-    MutexBlockErr(String),
     #[error("Error setting global event listener")]
     SendEventListenerError,
 }
 
-impl<T> From<PoisonError<MutexGuard<'_, T>>> for AppErr {
-    fn from(error: PoisonError<MutexGuard<'_, T>>) -> Self {
-        AppErr::MutexBlockErr(error.to_string())
+/// The game window and where to look in it.
+#[derive(Clone, Debug)]
+struct Game {
+    window: Window,
+    props: WindowProps,
+}
+
+impl Game {
+    fn status(game: &Option<Game>) -> WindowStatus {
+        match game {
+            Some(game) if game.window.is_active() => WindowStatus::Active,
+            Some(_) => WindowStatus::Inactive,
+            None => WindowStatus::Missing,
+        }
+    }
+}
+
+/// How long each thread gets to finish on exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the game window has to stay put after a move or resize before
+/// the props are computed again.
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// The thread that owns the hooks and pumps the messages they need.
+struct EventListener {
+    handle: JoinHandle<Result<()>>,
+    thread_id: u32,
+}
+
+impl EventListener {
+    const NAME: &'static str = "event listener";
+
+    fn start(swallowed_hotkeys: Vec<KeyCombo>) -> Result<Self> {
+        let (ready_sender, ready) = channel();
+        let handle = std::thread::Builder::new()
+            .spawn(move || -> Result<()> {
+                let set_hooks = || -> Result<_> {
+                    Ok((
+                        KeyboardEvent::new(swallowed_hotkeys.clone())?,
+                        ChangeForegroundWindow::new()?,
+                        DestroyWindow::new()?,
+                        LocationChange::new()?,
+                    ))
+                };
+                // Also creates the message queue, so a quit request can not get lost
+                let mut messenger = MessageManager::new();
+                let mut ready_sender = Some(ready_sender);
+                // Restarting reuses this thread, so a quit posted while it backs off still arrives
+                supervisor::supervise(
+                    Self::NAME,
+                    RestartPolicy::default(),
+                    |backoff| {
+                        std::thread::sleep(backoff);
+                        true
+                    },
+                    || {
+                        // The hooks are dropped, and so removed, on this thread once the loop ends
+                        let _hooks = match (set_hooks(), ready_sender.take()) {
+                            (Ok(hooks), ready) => {
+                                if let Some(ready) = ready {
+                                    let _ = ready.send(Ok(get_current_thread_id()));
+                                }
+                                hooks
+                            }
+                            // Failing to start is reported by `start` instead
+                            (Err(e), Some(ready)) => {
+                                let _ = ready.send(Err(e));
+                                return Ok(());
+                            }
+                            (Err(e), None) => return Err(e),
+                        };
+
+                        while messenger.get_message()? {
+                            messenger.translate_message();
+                            messenger.dispatch_message();
+                        }
+                        Ok(())
+                    },
+                )
+            })
+            .wrap_err("Error starting the event listener")?;
+
+        let thread_id = ready
+            .recv()
+            .wrap_err("Thread listener disconnected")?
+            .wrap_err("Error inside the event listener")?;
+        Ok(Self { handle, thread_id })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn stop(self) -> Result<()> {
+        // Fails if the thread is gone already, joining tells why
+        let _ = post_quit_to_thread(self.thread_id);
+        shutdown::join_timeout(Self::NAME, self.handle, STOP_TIMEOUT)
+    }
+}
+
+/// The thread that looks at the game and presses the keys.
+struct MessageSender {
+    handle: JoinHandle<Result<()>>,
+    target: Arc<TargetSlot<Game>>,
+}
+
+impl MessageSender {
+    const NAME: &'static str = "message sender";
+
+    fn start(config: &Config) -> Result<Self> {
+        let choice = config.option_choice;
+        let mut strategy = Strategy::new(config.strategy, config.skip_cutscenes);
+        let target = Arc::new(TargetSlot::new());
+        let skip_target = target.clone();
+        let handle = std::thread::Builder::new()
+            .spawn(move || -> Result<()> {
+                // Reused between ticks so the capture does not allocate every time
+                let mut grabber = GdiFrameGrabber::default();
+                let mut frame = FrameBuffer::default();
+                let mut announced = ChoiceState::default();
+                supervisor::supervise(
+                    Self::NAME,
+                    RestartPolicy::default(),
+                    |backoff| !skip_target.wait_closed(backoff),
+                    || {
+                        worker::run(&skip_target, Duration::from_millis(50), |game| {
+                            let Game { window, props } = game;
+                            window.grab_frame(props, &mut grabber, &mut frame)?;
+                            let reaction = strategy.react(&frame, props, choice)?;
+                            if strategy.choice_state() != announced {
+                                announced = strategy.choice_state();
+                                Self::announce(announced);
+                            }
+                            Ok(match reaction {
+                                Reaction::PressSpace => {
+                                    window.click_space()?;
+                                    DialogAction::PressSpace
+                                }
+                                Reaction::ClickOption(y) => {
+                                    window.click_option(props, y)?;
+                                    DialogAction::ClickOption
+                                }
+                                // Gives the game a tick to redraw the toggle
+                                Reaction::ToggleAutoPlay => {
+                                    window.click_auto_play(props)?;
+                                    DialogAction::Wait
+                                }
+                                Reaction::ClickCutscene(control) => {
+                                    window.click_cutscene(props, control)?;
+                                    DialogAction::Wait
+                                }
+                                // Looks again on the next tick, until the player has chosen
+                                Reaction::AwaitChoice | Reaction::Wait => DialogAction::Wait,
+                            })
+                        })
+                    },
+                )
+            })
+            .wrap_err("Error starting the message sender")?;
+
+        Ok(Self { handle, target })
+    }
+
+    /// Tells the player it is their turn, with the console bell so it is heard
+    /// over the game.
+    fn announce(state: ChoiceState) {
+        match state {
+            ChoiceState::AwaitingChoice => println!("\x07{state}"),
+            ChoiceState::Reading => println!("{state}"),
+        }
+    }
+
+    /// The game to work on, `None` to go idle.
+    fn set_target(&self, game: Option<Game>) {
+        self.target.set(game);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn stop(self) -> Result<()> {
+        self.target.close();
+        shutdown::join_timeout(Self::NAME, self.handle, STOP_TIMEOUT)
     }
 }
 
@@ -37,218 +235,189 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for AppErr {
 pub struct App;
 
 impl App {
-    pub fn run() -> Result<()> {
+    pub fn run(profiles: Vec<GameProfile>, config: Config) -> Result<()> {
         let (event_sender, event_receiver) = channel();
         // To receive messages from Windows Hook
         if EVENT_LISTENER_CHANNEL.set(event_sender).is_err() {
             return Err(AppErr::SendEventListenerError.into());
         };
 
-        Self::draw_menu();
+        Self::draw_menu(config.activation, &config.hotkeys);
+        // Work in physical pixels so probes and clicks line up on scaled monitors.
+        // This fails if the awareness was already set, which is just as good.
+        let _ = set_process_dpi_aware();
         // Receives shutdown messages
         ApplicationShutdown::set()?;
 
-        // To see if the stream is alive
-        let (stream_state_event_sender, stream_state_event_reciever) = channel();
-        let _event_listener = Self::start_event_listener(stream_state_event_sender)?;
+        let listener = EventListener::start(config.swallowed_hotkeys())?;
+        let sender = match MessageSender::start(&config) {
+            Ok(sender) => sender,
+            Err(e) => return shutdown::combine([Err(e), listener.stop()]),
+        };
 
-        stream_state_event_reciever
-            .recv()
-            .wrap_err("Thread listener disconnected")?
-            .wrap_err("Error inside the event listener")?;
+        let result = Self::event_loop(&event_receiver, &listener, &sender, &profiles, &config);
 
-        // Auxiliary data for working with genshin in a separate thread
-        let send_message = Arc::new(Mutex::new(false));
-        let genshin_window_props = Arc::new(Mutex::new(None));
-        let genshin_window = Arc::new(Mutex::new(None));
-
-        let (stream_state_message_sender, stream_state_message_reciever) = channel();
-
-        let _message_sender = Self::start_message_sender(
-            stream_state_message_sender,
-            send_message.clone(),
-            genshin_window_props.clone(),
-            genshin_window.clone(),
-        )?;
-
-        let find_genshin = || -> Result<()> {
-            let hwnd_result = Window::new();
-            match hwnd_result {
-                Ok(w) => *genshin_window.lock().map_err(AppErr::from)? = Some(w),
-                _ => *genshin_window.lock().map_err(AppErr::from)? = None,
+        // The sender goes first so it does not touch the game while the hooks go away
+        let result = shutdown::combine([result, sender.stop(), listener.stop()]);
+        println!("Ignored {} window events", WIN_EVENTS.dropped());
+        // Failed sends are only recorded, they are reported here once the hooks are gone
+        shutdown::combine([result, HOOK_ERRORS.check()])
+    }
+
+    fn event_loop(
+        events: &Receiver<EventType>,
+        listener: &EventListener,
+        sender: &MessageSender,
+        profiles: &[GameProfile],
+        config: &Config,
+    ) -> Result<()> {
+        // Name of the game last found, to say so when another one shows up
+        let mut found = None;
+        // The game window, checked again before looking through all the others
+        let mut known = None;
+        let mut find_game = || -> Result<Option<Game>> {
+            let game = match Window::find(profiles, known) {
+                Ok((window, profile)) => {
+                    if found != Some(profile.name()) {
+                        println!("Found {}", profile.name());
+                        found = Some(profile.name());
+                    }
+                    let (width, height) = window.client_size()?;
+                    let props = WindowProps::from_size(width, height, profile);
+                    Some(Game { window, props })
+                }
+                _ => None,
             };
-            Ok(())
+            known = game.as_ref().map(|game| game.window);
+            // Destroy events only matter for the window we have
+            WIN_EVENTS.set_game(
+                game.as_ref()
+                    .map(|game| (game.window.id(), game.window.process_id())),
+            );
+            Ok(game)
         };
 
-        let calculate_props = || -> Result<()> {
-            if let Some(w) = *genshin_window.lock().map_err(AppErr::from)? {
-                let window_props = WindowProps::new(&w)?;
-                *genshin_window_props.lock().map_err(AppErr::from)? = Some(window_props);
-            }
-            Ok(())
-        };
+        let mut skipper = Skipper::default();
+        let mut activation = Activation::new(config.activation, config.hotkeys);
+
+        let mut moved = Debounce::new(SETTLE_DELAY);
 
         // First attempt to find the window and calculate the properties
-        find_genshin()?;
-        calculate_props()?;
-        let mut enable_send_message = false;
-        while let Ok(event) = event_receiver.recv() {
-            match event {
-                EventType::KeyPress(key) => match key {
-                    // F9
-                    120 if !enable_send_message => {
-                        enable_send_message = true;
-                    }
-                    // F10
-                    121 if enable_send_message => {
-                        enable_send_message = false;
+        let mut game = Self::or_retry(find_game(), &mut moved);
+        skipper.on_input(Input::Window(Game::status(&game)));
+
+        loop {
+            // While the game window is moved, also wake up once it has settled
+            let event = match moved.deadline() {
+                Some(deadline) => {
+                    match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(event) => Some(event),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
                     }
-                    // F11
-                    122 => break,
-                    _ => {
+                }
+                None => match events.recv() {
+                    Ok(event) => Some(event),
+                    Err(_) => break,
+                },
+            };
+
+            let input = match event {
+                None => {
+                    if !moved.fire(Instant::now()) {
                         continue;
                     }
-                },
-                EventType::ChangeForegroundWindow => {
-                    find_genshin()?;
-                    calculate_props()?;
+                    game = Self::or_retry(find_game(), &mut moved);
+                    Input::Window(Game::status(&game))
                 }
-                EventType::DestroyWindow => {
-                    find_genshin()?;
-                    calculate_props()?;
+                Some(EventType::Key(key_event)) => {
+                    match activation.on_key(key_event, skipper.state()) {
+                        Some(Command::Start) => Input::Start,
+                        Some(Command::Stop) => Input::Stop,
+                        Some(Command::Exit) => Input::Shutdown,
+                        None => continue,
+                    }
                 }
-                EventType::Shutdown => break,
-            }
-
-            // If an error occurred in the event listener or it is no longer available
-            match stream_state_event_reciever.try_recv() {
-                Err(TryRecvError::Disconnected) => {
-                    return Err(Report::new(TryRecvError::Disconnected)
-                        .wrap_err("Thread listener disconnected"));
+                Some(
+                    EventType::ChangeForegroundWindow(event) | EventType::DestroyWindow(event),
+                ) => {
+                    WIN_EVENTS.handled(event.kind);
+                    game = Self::or_retry(find_game(), &mut moved);
+                    Input::Window(Game::status(&game))
                 }
-                Ok(e) => e?,
-                _ => {}
-            }
-
-            // If an error occurred in the messeg sender thread or it is no longer available
-            match stream_state_message_reciever.try_recv() {
-                Err(TryRecvError::Disconnected) => {
-                    return Err(Report::new(TryRecvError::Disconnected)
-                        .wrap_err("Thread send message disconnected"))
+                Some(EventType::LocationChange(event)) => {
+                    WIN_EVENTS.handled(event.kind);
+                    moved.on_event(Instant::now());
+                    continue;
                 }
-                Ok(e) => e?,
-                _ => {}
-            }
-
-            // Checking whether the genshin is working and whether the key is pressed
-            *send_message.lock().map_err(AppErr::from)? = if enable_send_message {
-                if let Some(w) = &*genshin_window.lock().map_err(AppErr::from)? {
-                    genshin_window_props.lock().map_err(AppErr::from)?.is_some() && w.is_active()
-                } else {
-                    false
+                Some(EventType::Shutdown) => Input::Shutdown,
+                Some(EventType::HookFailed) => {
+                    HOOK_ERRORS.check()?;
+                    continue;
                 }
-            } else {
-                false
             };
-        }
-
-        Ok(())
-    }
 
-    fn start_event_listener(sender: Sender<Result<()>>) -> Result<JoinHandle<Result<()>>> {
-        let handle = std::thread::Builder::new()
-            .spawn(move || -> Result<(), Report> {
-                let run = || -> Result<(), Report> {
-                    let _h1 = KeyboardEvent::new()?;
-                    let _h2 = ChangeForegroundWindow::new()?;
-                    let _h3 = DestroyWindow::new()?;
-
-                    sender.send(Ok(()))?;
-                    let mut messenger = MessageManager::new();
-
-                    while messenger.get_message().is_ok() {
-                        messenger.translate_message()?;
-                        messenger.dispatch_message();
-                    }
+            if let Some(transition) = skipper.on_input(input) {
+                println!("{}", transition.to);
+            }
+            if skipper.state() == SkipperState::ShuttingDown {
+                break;
+            }
 
-                    Ok(())
-                };
+            // What went wrong inside is reported when the thread is joined
+            if listener.is_finished() {
+                return Err(shutdown::stopped_unexpectedly(EventListener::NAME));
+            }
+            if sender.is_finished() {
+                return Err(shutdown::stopped_unexpectedly(MessageSender::NAME));
+            }
 
-                if let Err(e) = run() {
-                    sender.send(Err(e))?;
-                }
+            sender.set_target(match skipper.state() {
+                SkipperState::Skipping => game.clone(),
+                _ => None,
+            });
+        }
 
-                Ok(())
-            })
-            .wrap_err("Error starting the event listener")?;
-        Ok(handle)
+        Ok(())
     }
 
-    fn start_message_sender(
-        sender: Sender<Result<()>>,
-        send_message: Arc<Mutex<bool>>,
-        genshin_window_props: Arc<Mutex<Option<WindowProps>>>,
-        genshin_window: Arc<Mutex<Option<Window>>>,
-    ) -> Result<JoinHandle<Result<()>>> {
-        let handle = std::thread::Builder::new()
-            .spawn(move || -> Result<(), Report> {
-                let run = || -> Result<()> {
-                    loop {
-                        if !*send_message.lock().map_err(AppErr::from)? {
-                            std::thread::sleep(Duration::from_millis(50));
-                            continue;
-                        };
-
-                        let genshin_window = *genshin_window.lock().map_err(AppErr::from)?;
-                        let window_props = *genshin_window_props.lock().map_err(AppErr::from)?;
-
-                        if let Some(window) = genshin_window {
-                            if let Some(win_props) = window_props {
-                                if window.dialog_played(&win_props)? {
-                                    // If the character is talking and you don't need to select anything,
-                                    // we send the space bar to avoid pulling the cursor.
-                                    if window.is_dialog_without_option(&win_props)? {
-                                        window.click_space()?;
-                                    } else {
-                                        window.click_left_m_button_random_pos(&win_props)?;
-                                    }
-                                } else {
-                                    std::thread::sleep(Duration::from_millis(50));
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                };
-
-                if let Err(e) = run() {
-                    sender.send(Err(e))?;
-                }
-
-                Ok(())
-            })
-            .wrap_err("Error starting the message sender")?;
-
-        Ok(handle)
+    /// A window that cannot be read right now, mid resolution change say, is
+    /// looked at again once it has settled instead of ending the app.
+    fn or_retry(game: Result<Option<Game>>, retry: &mut Debounce) -> Option<Game> {
+        game.unwrap_or_else(|e| {
+            eprintln!("Could not read the game window, looking again shortly: {e:#}");
+            retry.on_event(Instant::now());
+            None
+        })
     }
 
-    fn draw_menu() {
-        let menu = r"
-        ==================================
-        |    Genshin Auto-Skip Dialogs   |
-        ==================================
-        
-        **********************************
-        *          KEY BINDINGS          *
-        **********************************
-        * F9  - Run                      *
-        * F10 - Pause                    *
-        * F11 - Exit                     *
-        **********************************
-        ";
-
-        for line in menu.trim().lines() {
-            println!("{}", line.trim());
+    fn draw_menu(mode: ActivationMode, hotkeys: &HotkeyBindings) {
+        const WIDTH: usize = 34;
+        let title = format!("|{:^1$}|", "Genshin Auto-Skip Dialogs", WIDTH - 2);
+        let border = "*".repeat(WIDTH);
+
+        println!("{}", "=".repeat(WIDTH));
+        println!("{title}");
+        println!("{}", "=".repeat(WIDTH));
+        println!();
+        println!("{border}");
+        println!("*{:^1$}*", "KEY BINDINGS", WIDTH - 2);
+        println!("{border}");
+
+        let combos: Vec<_> = hotkeys
+            .iter()
+            .filter_map(|(action, combo)| Some((mode.describe(action)?, combo.to_string())))
+            .collect();
+        let key_width = combos
+            .iter()
+            .map(|(_, combo)| combo.len())
+            .max()
+            .unwrap_or(0);
+        for (action, combo) in combos {
+            let line = format!("{combo:<key_width$} - {action}");
+            // Long combinations push the right border out rather than being cut
+            println!("* {line:<0$} *", WIDTH - 4);
         }
+        println!("{border}");
     }
 }
@@ -0,0 +1,267 @@
+use crate::pixel_source::FrameBuffer;
+use color_eyre::Result;
+
+#[cfg(windows)]
+use crate::winapi_bindings::bindings::{capture_window, get_client_size};
+#[cfg(windows)]
+use crate::{coords::Rect, pixel_source::PixelSourceErr};
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+
+/// Grabs what detection needs in one go so every detector can read from memory
+/// instead of asking the system for each pixel.
+pub trait FrameGrabber {
+    fn grab(&mut self, frame: &mut FrameBuffer) -> Result<()>;
+}
+
+/// Copies the window into a DIB with `BitBlt` once per tick.
+#[cfg(windows)]
+#[derive(Debug, Default)]
+pub struct GdiFrameGrabber {
+    hwnd: usize,
+    /// Parts of the client area to copy, all of it if empty.
+    areas: Vec<Rect>,
+    /// `areas` cut to the current client area.
+    clipped: Vec<Rect>,
+    bgra: Vec<u8>,
+}
+
+#[cfg(windows)]
+impl GdiFrameGrabber {
+    /// Points the grabber at another window, keeping the pixel storage.
+    pub fn set_window(&mut self, hwnd: HWND) {
+        self.hwnd = hwnd as usize;
+    }
+
+    /// Only copies `areas` of the client area, all of it if there are none.
+    /// Detection reads a few small spots, even the box around all of them is
+    /// a megabyte of pixels to convert every tick at high resolutions.
+    pub fn set_areas(&mut self, areas: impl IntoIterator<Item = Rect>) {
+        self.areas.clear();
+        self.areas.extend(areas);
+    }
+}
+
+#[cfg(windows)]
+impl FrameGrabber for GdiFrameGrabber {
+    fn grab(&mut self, frame: &mut FrameBuffer) -> Result<()> {
+        let hwnd = self.hwnd as HWND;
+        let (width, height) = get_client_size(hwnd).map_err(PixelSourceErr::Unreadable)?;
+        let client = Rect {
+            left: 0,
+            top: 0,
+            right: width,
+            bottom: height,
+        };
+        // The window may have shrunk since the areas were worked out
+        self.clipped.clear();
+        if self.areas.is_empty() {
+            self.clipped.push(client);
+        }
+        self.clipped.extend(
+            self.areas
+                .iter()
+                .map(|area| area.intersect(client))
+                .filter(|area| !area.is_empty()),
+        );
+        if self.clipped.is_empty() || client.is_empty() {
+            self.clipped.clear();
+            self.bgra.clear();
+        } else {
+            capture_window(hwnd, &self.clipped, &mut self.bgra)
+                .map_err(PixelSourceErr::Unreadable)?;
+        }
+        frame.fill_from_bgra(&self.clipped, &self.bgra)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        coords::Rect,
+        genshin::{next_action, DialogAction, WindowProps},
+        pixel_source::{PixelSource, Rgb},
+        profile::GameProfile,
+    };
+    use std::cell::Cell;
+    #[cfg(windows)]
+    use std::time::{Duration, Instant};
+
+    /// Stands in for the game window, counting the reads.
+    struct SyntheticScreen {
+        frame: FrameBuffer,
+        calls: Cell<usize>,
+    }
+
+    impl SyntheticScreen {
+        fn dialogue() -> Self {
+            let mut frame = FrameBuffer::new(1920, 1080, (40, 60, 90));
+            frame.fill_rect(72, 34, 24, 24, (236, 229, 216));
+            Self {
+                frame,
+                calls: Cell::new(0),
+            }
+        }
+    }
+
+    impl PixelSource for SyntheticScreen {
+        fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb> {
+            self.calls.set(self.calls.get() + 1);
+            self.frame.get_pixel(x, y)
+        }
+
+        fn size(&self) -> Result<(i32, i32)> {
+            self.frame.size()
+        }
+    }
+
+    impl FrameGrabber for SyntheticScreen {
+        fn grab(&mut self, frame: &mut FrameBuffer) -> Result<()> {
+            self.calls.set(self.calls.get() + 1);
+            frame.clone_from(&self.frame);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn grabbed_frame_gives_the_same_verdict() {
+        let mut screen = SyntheticScreen::dialogue();
+        let props = WindowProps::new(&screen, &GameProfile::default()).unwrap();
+        let per_pixel = next_action(&screen, &props).unwrap();
+        let probes = screen.calls.replace(0);
+
+        let mut frame = FrameBuffer::default();
+        screen.grab(&mut frame).unwrap();
+        assert_eq!(next_action(&frame, &props).unwrap(), per_pixel);
+        assert_eq!(per_pixel, DialogAction::PressSpace);
+        assert_eq!(screen.calls.get(), 1);
+        assert!(probes > 1);
+    }
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn bgra_is_converted_to_rgb() {
+        let mut frame = FrameBuffer::new(3, 3, (0, 0, 0));
+        frame
+            .fill_from_bgra(&[rect(0, 0, 2, 1)], &[1, 2, 3, 0, 4, 5, 6, 0])
+            .unwrap();
+        assert_eq!(frame.size().unwrap(), (2, 1));
+        assert_eq!(frame.get_pixel(0, 0).unwrap(), (3, 2, 1));
+        assert_eq!(frame.get_pixel(1, 0).unwrap(), (6, 5, 4));
+        assert!(frame.fill_from_bgra(&[rect(0, 0, 2, 2)], &[0; 8]).is_err());
+    }
+
+    /// Copies `areas` of `frame` the way the GDI grabber would.
+    fn grab_areas(frame: &FrameBuffer, areas: &[Rect]) -> FrameBuffer {
+        let width = areas.iter().map(Rect::width).max().unwrap_or(0);
+        let mut bgra = Vec::new();
+        for area in areas {
+            for y in area.top..area.bottom {
+                for x in area.left..area.left + width {
+                    let (r, g, b) = frame.get_pixel(x, y).unwrap_or_default();
+                    bgra.extend([b, g, r, 0]);
+                }
+            }
+        }
+        let mut grabbed = FrameBuffer::default();
+        grabbed.fill_from_bgra(areas, &bgra).unwrap();
+        grabbed
+    }
+
+    #[test]
+    fn partial_frame_keeps_window_coordinates() {
+        let mut frame = FrameBuffer::new(100, 100, (0, 0, 0));
+        frame.set_pixel(60, 70, (1, 2, 3)).unwrap();
+        frame.set_pixel(12, 95, (4, 5, 6)).unwrap();
+        let grabbed = grab_areas(&frame, &[rect(50, 60, 80, 90), rect(10, 94, 13, 96)]);
+        assert_eq!(grabbed.area(), rect(10, 60, 80, 96));
+        assert_eq!(grabbed.get_pixel(60, 70).unwrap(), (1, 2, 3));
+        assert_eq!(grabbed.get_pixel(12, 95).unwrap(), (4, 5, 6));
+        // Between the parts nothing was copied
+        assert!(grabbed.get_pixel(10, 10).is_err());
+        assert!(grabbed.get_pixel(30, 70).is_err());
+    }
+
+    #[test]
+    fn capture_areas_are_enough_to_detect() {
+        let screen = SyntheticScreen::dialogue();
+        for (width, height) in [(1920, 1080), (2560, 1440), (3840, 2160)] {
+            let props = WindowProps::from_size(width, height, &GameProfile::default());
+            let areas: Vec<_> = props.capture_areas().collect();
+            let pixels: i32 = areas.iter().map(|area| area.width() * area.height()).sum();
+            // A few spots and a line, rather than the box around them
+            assert!(
+                pixels * 1000 < width * height,
+                "{pixels} pixels in {width}x{height}"
+            );
+            for area in areas {
+                assert!(
+                    area.left > 0 && area.top > 0 && area.right < width && area.bottom < height,
+                    "{area:?} in {width}x{height}"
+                );
+            }
+        }
+
+        let props = WindowProps::from_size(1920, 1080, &GameProfile::default());
+        let areas: Vec<_> = props.capture_areas().collect();
+        let grabbed = grab_areas(&screen.frame, &areas);
+        assert_eq!(
+            next_action(&grabbed, &props).unwrap(),
+            DialogAction::PressSpace
+        );
+        assert_eq!(
+            props.find_options(&grabbed).unwrap(),
+            props.find_options(&screen.frame).unwrap()
+        );
+    }
+
+    /// Ticks per second reading the desktop with `GetPixel` for every
+    /// sample, then with one capture of the areas per tick. The desktop stands
+    /// in for the game window, GDI reads it the same way.
+    ///
+    /// `cargo test --release -- --ignored --nocapture bench_gdi_reads`
+    #[cfg(windows)]
+    #[test]
+    #[ignore = "benchmark, reads the desktop"]
+    fn bench_gdi_reads() {
+        use crate::{pixel_source::GdiPixelSource, winapi_bindings::bindings::get_desktop_window};
+
+        const DURATION: Duration = Duration::from_secs(2);
+        fn ticks_per_second(mut tick: impl FnMut()) -> f64 {
+            let start = Instant::now();
+            let mut ticks = 0;
+            while start.elapsed() < DURATION {
+                tick();
+                ticks += 1;
+            }
+            f64::from(ticks) / start.elapsed().as_secs_f64()
+        }
+
+        let desktop = get_desktop_window();
+        let per_pixel = GdiPixelSource::new(desktop);
+        let props = WindowProps::new(&per_pixel, &GameProfile::default()).unwrap();
+
+        let pixel_ticks = ticks_per_second(|| {
+            next_action(&per_pixel, &props).unwrap();
+        });
+        let mut grabber = GdiFrameGrabber::default();
+        grabber.set_window(desktop);
+        grabber.set_areas(props.capture_areas());
+        let mut frame = FrameBuffer::default();
+        let frame_ticks = ticks_per_second(|| {
+            grabber.grab(&mut frame).unwrap();
+            next_action(&frame, &props).unwrap();
+        });
+        println!("per-pixel {pixel_ticks:>8.0} ticks/s");
+        println!("frame     {frame_ticks:>8.0} ticks/s");
+    }
+}
@@ -0,0 +1,150 @@
+use color_eyre::Result;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CliErr {
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+    #[error("The replay command needs at least one file or folder")]
+    NoReplayInput,
+    #[error("{0} needs a value")]
+    MissingValue(String),
+    #[error("--game and --profile can not be used together")]
+    GameAndProfile,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Hook into the game and skip dialogues.
+    Run,
+    /// Run the detection against screenshots instead of the live window.
+    Replay(Vec<PathBuf>),
+    /// Print a built-in game profile, as a starting point for a custom one.
+    DefaultProfile,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cli {
+    pub command: Command,
+    /// Built-in profile to use instead of the auto-detected ones.
+    pub game: Option<String>,
+    /// Game profile to use instead of the built-in ones.
+    pub profile: Option<PathBuf>,
+    /// Hotkeys and other settings, defaults are used without it.
+    pub config: Option<PathBuf>,
+}
+
+impl Cli {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut game = None;
+        let mut profile = None;
+        let mut config = None;
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !matches!(arg.as_str(), "--game" | "--profile" | "--config") {
+                positional.push(arg);
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| CliErr::MissingValue(arg.clone()))?;
+            match arg.as_str() {
+                "--game" => game = Some(value),
+                "--profile" => profile = Some(PathBuf::from(value)),
+                _ => config = Some(PathBuf::from(value)),
+            }
+        }
+        if game.is_some() && profile.is_some() {
+            return Err(CliErr::GameAndProfile.into());
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            None => Command::Run,
+            Some("replay") => {
+                let paths: Vec<PathBuf> = positional.map(PathBuf::from).collect();
+                if paths.is_empty() {
+                    return Err(CliErr::NoReplayInput.into());
+                }
+                Command::Replay(paths)
+            }
+            Some("default-profile") => Command::DefaultProfile,
+            Some(other) => return Err(CliErr::UnknownCommand(other.to_string()).into()),
+        };
+
+        Ok(Self {
+            command,
+            game,
+            profile,
+            config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli> {
+        Cli::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn runs_by_default() {
+        let cli = parse(&[]).unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(cli.game, None);
+        assert_eq!(cli.profile, None);
+        assert_eq!(cli.config, None);
+    }
+
+    #[test]
+    fn profile_can_go_anywhere() {
+        let expected = Cli {
+            command: Command::Replay(vec![PathBuf::from("shots")]),
+            game: None,
+            profile: Some(PathBuf::from("custom.toml")),
+            config: None,
+        };
+        assert_eq!(
+            parse(&["--profile", "custom.toml", "replay", "shots"]).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse(&["replay", "shots", "--profile", "custom.toml"]).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn reads_the_config_path() {
+        let cli = parse(&["--config", "skipper.toml"]).unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(cli.config, Some(PathBuf::from("skipper.toml")));
+    }
+
+    #[test]
+    fn picks_a_builtin_game() {
+        let cli = parse(&["default-profile", "--game", "star-rail"]).unwrap();
+        assert_eq!(cli.command, Command::DefaultProfile);
+        assert_eq!(cli.game.as_deref(), Some("star-rail"));
+
+        let err = parse(&["--game", "genshin", "--profile", "custom.toml"]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CliErr>(),
+            Some(CliErr::GameAndProfile)
+        ));
+    }
+
+    #[test]
+    fn rejects_incomplete_arguments() {
+        assert!(parse(&["replay"]).is_err());
+        assert!(parse(&["--game"]).is_err());
+        assert!(parse(&["--profile"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["skip"]).is_err());
+    }
+}
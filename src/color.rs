@@ -0,0 +1,89 @@
+use crate::pixel_source::Rgb;
+use serde::Deserialize;
+
+/// How close a sampled pixel has to be to the expected colour.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMatcher {
+    /// Every channel must be identical.
+    Exact,
+    /// Every channel may differ by at most this much.
+    PerChannel(u8),
+    /// The straight-line distance in RGB space may be at most this much.
+    Euclidean(f32),
+}
+
+impl ColorMatcher {
+    /// Small enough to keep the near-white open-world sky apart from pure white,
+    /// large enough to survive colour filters and capture compression.
+    pub const DEFAULT_DISTANCE: f32 = 8.0;
+
+    pub fn matches(&self, actual: Rgb, expected: Rgb) -> bool {
+        match *self {
+            ColorMatcher::Exact => actual == expected,
+            ColorMatcher::PerChannel(tolerance) => channel_deltas(actual, expected)
+                .iter()
+                .all(|delta| *delta <= u32::from(tolerance)),
+            ColorMatcher::Euclidean(max_distance) => distance(actual, expected) <= max_distance,
+        }
+    }
+}
+
+impl Default for ColorMatcher {
+    fn default() -> Self {
+        ColorMatcher::Euclidean(Self::DEFAULT_DISTANCE)
+    }
+}
+
+pub fn distance(a: Rgb, b: Rgb) -> f32 {
+    let squared: u32 = channel_deltas(a, b).iter().map(|delta| delta * delta).sum();
+    (squared as f32).sqrt()
+}
+
+fn channel_deltas(a: Rgb, b: Rgb) -> [u32; 3] {
+    [
+        u32::from(a.0.abs_diff(b.0)),
+        u32::from(a.1.abs_diff(b.1)),
+        u32::from(a.2.abs_diff(b.2)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgb = (255, 255, 255);
+    const OPEN_WORLD_SKY: Rgb = (246, 249, 252);
+
+    #[test]
+    fn exact_rejects_any_difference() {
+        assert!(ColorMatcher::Exact.matches(WHITE, WHITE));
+        assert!(!ColorMatcher::Exact.matches((254, 255, 255), WHITE));
+    }
+
+    #[test]
+    fn per_channel_checks_the_largest_delta() {
+        let matcher = ColorMatcher::PerChannel(4);
+        assert!(matcher.matches((232, 233, 212), (236, 229, 216)));
+        assert!(!matcher.matches((231, 229, 216), (236, 229, 216)));
+    }
+
+    #[test]
+    fn euclidean_combines_channels() {
+        let matcher = ColorMatcher::Euclidean(5.0);
+        assert!(matcher.matches((252, 251, 255), WHITE));
+        assert!(!matcher.matches((252, 251, 252), WHITE));
+    }
+
+    #[test]
+    fn default_accepts_filtered_colors() {
+        let matcher = ColorMatcher::default();
+        assert!(matcher.matches((251, 253, 249), WHITE));
+        assert!(matcher.matches((240, 226, 219), (236, 229, 216)));
+    }
+
+    #[test]
+    fn default_rejects_open_world_sky() {
+        assert!(!ColorMatcher::default().matches(OPEN_WORLD_SKY, WHITE));
+    }
+}
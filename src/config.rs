@@ -0,0 +1,182 @@
+use crate::{
+    activation::ActivationMode,
+    hotkeys::{HotkeyBindings, KeyCombo},
+    options::OptionChoice,
+    profile::GameProfile,
+    strategy::SkipStrategy,
+};
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigErr {
+    #[error("skip_cutscenes is on, but the {0} profile has no [cutscene] section to find the skip control with")]
+    NoCutsceneControls(String),
+    #[error("The auto-play strategy is set, but the {0} profile has no [auto_play] section to find the toggle with")]
+    NoAutoPlayToggle(String),
+}
+
+/// How the skipper is controlled, as opposed to the game profile which
+/// describes what it looks for.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub activation: ActivationMode,
+    pub hotkeys: HotkeyBindings,
+    /// Keep our hotkeys from reaching the game, F11 toggles its fullscreen otherwise.
+    pub swallow_hotkeys: bool,
+    /// Which dialogue option to click.
+    pub option_choice: OptionChoice,
+    /// How lines without options are skipped.
+    pub strategy: SkipStrategy,
+    /// Skip cutscenes that have a skip control, off as some players want to watch them.
+    pub skip_cutscenes: bool,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).wrap_err("Error parsing the config")?;
+        config.hotkeys.validate()?;
+        Ok(config)
+    }
+
+    /// Hotkeys the keyboard hook should keep to itself. Only the ones the
+    /// activation mode uses, an unused pause key still reaches the game.
+    pub fn swallowed_hotkeys(&self) -> Vec<KeyCombo> {
+        if !self.swallow_hotkeys {
+            return Vec::new();
+        }
+        self.hotkeys
+            .iter()
+            .filter(|(action, _)| self.activation.describe(*action).is_some())
+            .map(|(_, combo)| combo)
+            .collect()
+    }
+
+    /// Refuses settings a profile cannot carry out, instead of quietly skipping
+    /// less than asked for.
+    pub fn check_profiles(&self, profiles: &[GameProfile]) -> Result<(), ConfigErr> {
+        for profile in profiles {
+            if self.skip_cutscenes && profile.cutscene().is_none() {
+                return Err(ConfigErr::NoCutsceneControls(profile.name().to_string()));
+            }
+            if self.strategy == SkipStrategy::AutoPlay && profile.auto_play().is_none() {
+                return Err(ConfigErr::NoAutoPlayToggle(profile.name().to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Error reading {}", path.display()))?;
+        Self::parse(&text).wrap_err_with(|| format!("Invalid config {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{AUTO_PLAY_EXAMPLE, CUTSCENE_EXAMPLE};
+
+    #[test]
+    fn missing_hotkeys_keep_their_defaults() {
+        let config = Config::parse(
+            r#"
+            [hotkeys]
+            run = "Ctrl+Alt+S"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.hotkeys.run,
+            "Ctrl+Alt+S".parse::<KeyCombo>().unwrap()
+        );
+        assert_eq!(config.hotkeys.exit, HotkeyBindings::default().exit);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn reads_the_activation_mode() {
+        let config = Config::parse("activation = \"hold\"").unwrap();
+        assert_eq!(config.activation, ActivationMode::Hold);
+        assert_eq!(Config::default().activation, ActivationMode::RunPause);
+        assert!(Config::parse("activation = \"sometimes\"").is_err());
+    }
+
+    #[test]
+    fn reads_the_option_choice() {
+        assert_eq!(Config::default().option_choice, OptionChoice::Last);
+        let config = Config::parse("option_choice = { index = 2 }").unwrap();
+        assert_eq!(config.option_choice.pick(&[600, 700, 800]), Some(700));
+        assert!(Config::parse("option_choice = \"random\"").is_err());
+    }
+
+    #[test]
+    fn reads_the_strategy() {
+        assert_eq!(Config::default().strategy, SkipStrategy::PressSpace);
+        let config = Config::parse("strategy = \"auto-play\"").unwrap();
+        assert_eq!(config.strategy, SkipStrategy::AutoPlay);
+        assert!(!config.skip_cutscenes);
+        assert!(
+            Config::parse("skip_cutscenes = true")
+                .unwrap()
+                .skip_cutscenes
+        );
+    }
+
+    #[test]
+    fn refuses_to_skip_cutscenes_without_the_controls() {
+        let config = Config::parse("skip_cutscenes = true").unwrap();
+        let builtin = [GameProfile::default()];
+        let err = config.check_profiles(&builtin).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "skip_cutscenes is on, but the Genshin Impact profile has no [cutscene] section to find the skip control with"
+        );
+        assert!(Config::default().check_profiles(&builtin).is_ok());
+
+        let with_controls = GameProfile::genshin_with(CUTSCENE_EXAMPLE);
+        assert!(config.check_profiles(&[with_controls]).is_ok());
+    }
+
+    #[test]
+    fn refuses_auto_play_without_the_toggle() {
+        let config = Config::parse("strategy = \"auto-play\"").unwrap();
+        let err = config
+            .check_profiles(&[GameProfile::default()])
+            .unwrap_err();
+        assert!(
+            matches!(&err, ConfigErr::NoAutoPlayToggle(name) if name == "Genshin Impact"),
+            "{err}"
+        );
+
+        let with_toggle = GameProfile::genshin_with(AUTO_PLAY_EXAMPLE);
+        assert!(config.check_profiles(&[with_toggle]).is_ok());
+    }
+
+    #[test]
+    fn swallows_only_the_hotkeys_in_use() {
+        let mut config = Config::default();
+        assert!(config.swallowed_hotkeys().is_empty());
+
+        config.swallow_hotkeys = true;
+        let bindings = config.hotkeys;
+        assert_eq!(
+            config.swallowed_hotkeys(),
+            [bindings.run, bindings.pause, bindings.exit]
+        );
+
+        config.activation = ActivationMode::Toggle;
+        assert_eq!(config.swallowed_hotkeys(), [bindings.run, bindings.exit]);
+    }
+
+    #[test]
+    fn invalid_hotkeys_are_reported() {
+        assert!(Config::parse("[hotkeys]\nrun = \"Ctrl+Hyper+S\"").is_err());
+        assert!(Config::parse("[hotkeys]\nrun = \"F11\"").is_err());
+        assert!(Config::parse("[hotkeys]\nstart = \"F1\"").is_err());
+    }
+}
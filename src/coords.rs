@@ -0,0 +1,327 @@
+/// DPI at 100% scaling. Coordinates of DPI-unaware windows are in these units.
+pub const DEFAULT_DPI: u32 = 96;
+
+/// A point relative to the top-left corner of the virtual screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScreenPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A point relative to the outer frame of a window (title bar and borders included).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A point relative to the drawable area of a window. Pixel reads, captures and
+/// mouse messages all use these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+
+    /// The smallest rectangle holding both.
+    pub fn union(self, other: Rect) -> Rect {
+        Rect {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// The part of both, empty rather than inside out if they do not overlap.
+    pub fn intersect(self, other: Rect) -> Rect {
+        let left = self.left.max(other.left);
+        let top = self.top.max(other.top);
+        Rect {
+            left,
+            top,
+            right: self.right.min(other.right).max(left),
+            bottom: self.bottom.min(other.bottom).max(top),
+        }
+    }
+}
+
+/// Where a window is on screen and how its coordinates relate to ours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowGeometry {
+    /// Outer frame in screen coordinates.
+    pub window: Rect,
+    /// Drawable area in screen coordinates.
+    pub client: Rect,
+    /// DPI of the monitor the window is on.
+    pub dpi: u32,
+    /// Whether this process sees physical pixels. If not, every coordinate the
+    /// system hands us is scaled down to 96 DPI.
+    pub caller_dpi_aware: bool,
+    /// Whether the window itself works in physical pixels.
+    pub window_dpi_aware: bool,
+}
+
+impl WindowGeometry {
+    pub fn client_size(&self) -> (i32, i32) {
+        (self.client.width(), self.client.height())
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        f64::from(self.dpi) / f64::from(DEFAULT_DPI)
+    }
+
+    pub fn client_to_screen(&self, point: ClientPoint) -> ScreenPoint {
+        ScreenPoint {
+            x: point.x + self.client.left,
+            y: point.y + self.client.top,
+        }
+    }
+
+    pub fn screen_to_client(&self, point: ScreenPoint) -> ClientPoint {
+        ClientPoint {
+            x: point.x - self.client.left,
+            y: point.y - self.client.top,
+        }
+    }
+
+    pub fn window_to_screen(&self, point: WindowPoint) -> ScreenPoint {
+        ScreenPoint {
+            x: point.x + self.window.left,
+            y: point.y + self.window.top,
+        }
+    }
+
+    pub fn screen_to_window(&self, point: ScreenPoint) -> WindowPoint {
+        WindowPoint {
+            x: point.x - self.window.left,
+            y: point.y - self.window.top,
+        }
+    }
+
+    pub fn client_to_window(&self, point: ClientPoint) -> WindowPoint {
+        self.screen_to_window(self.client_to_screen(point))
+    }
+
+    pub fn window_to_client(&self, point: WindowPoint) -> ClientPoint {
+        self.screen_to_client(self.window_to_screen(point))
+    }
+
+    /// Client point as the window reads it from a mouse message.
+    ///
+    /// The system does not rescale message parameters, so when only one side is
+    /// DPI aware the point has to be converted by hand.
+    pub fn client_to_message(&self, point: ClientPoint) -> ClientPoint {
+        match (self.caller_dpi_aware, self.window_dpi_aware) {
+            (false, true) => ClientPoint {
+                x: logical_to_physical(point.x, self.dpi),
+                y: logical_to_physical(point.y, self.dpi),
+            },
+            (true, false) => ClientPoint {
+                x: physical_to_logical(point.x, self.dpi),
+                y: physical_to_logical(point.y, self.dpi),
+            },
+            _ => point,
+        }
+    }
+}
+
+/// Same rounding as `MulDiv`.
+fn mul_div(value: i32, numerator: u32, denominator: u32) -> i32 {
+    let (numerator, denominator) = (i64::from(numerator), i64::from(denominator));
+    let product = i64::from(value) * numerator;
+    let rounded = if product >= 0 {
+        (product + denominator / 2) / denominator
+    } else {
+        (product - denominator / 2) / denominator
+    };
+    rounded as i32
+}
+
+pub fn logical_to_physical(value: i32, dpi: u32) -> i32 {
+    mul_div(value, dpi, DEFAULT_DPI)
+}
+
+pub fn physical_to_logical(value: i32, dpi: u32) -> i32 {
+    mul_div(value, DEFAULT_DPI, dpi.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1600x900 client on the second monitor, with an 8px border and a 31px title bar.
+    fn windowed() -> WindowGeometry {
+        WindowGeometry {
+            window: Rect {
+                left: 1912,
+                top: 100,
+                right: 3528,
+                bottom: 1039,
+            },
+            client: Rect {
+                left: 1920,
+                top: 131,
+                right: 3520,
+                bottom: 1031,
+            },
+            dpi: 144,
+            caller_dpi_aware: true,
+            window_dpi_aware: true,
+        }
+    }
+
+    #[test]
+    fn rects_combine() {
+        let a = Rect {
+            left: 0,
+            top: 0,
+            right: 10,
+            bottom: 10,
+        };
+        let b = Rect {
+            left: 5,
+            top: 8,
+            right: 20,
+            bottom: 30,
+        };
+        assert_eq!(
+            a.union(b),
+            Rect {
+                left: 0,
+                top: 0,
+                right: 20,
+                bottom: 30,
+            }
+        );
+        assert_eq!(
+            a.intersect(b),
+            Rect {
+                left: 5,
+                top: 8,
+                right: 10,
+                bottom: 10,
+            }
+        );
+        let apart = Rect {
+            left: 50,
+            top: 50,
+            right: 60,
+            bottom: 60,
+        };
+        assert!(a.intersect(apart).is_empty());
+        assert!(!a.is_empty());
+    }
+
+    #[test]
+    fn client_size_excludes_the_frame() {
+        let geometry = windowed();
+        assert_eq!(geometry.client_size(), (1600, 900));
+        assert_eq!(
+            (geometry.window.width(), geometry.window.height()),
+            (1616, 939)
+        );
+    }
+
+    #[test]
+    fn client_and_screen_round_trip() {
+        let geometry = windowed();
+        let client = ClientPoint { x: 1300, y: 790 };
+        let screen = geometry.client_to_screen(client);
+        assert_eq!(screen, ScreenPoint { x: 3220, y: 921 });
+        assert_eq!(geometry.screen_to_client(screen), client);
+    }
+
+    #[test]
+    fn client_and_window_round_trip() {
+        let geometry = windowed();
+        let client = ClientPoint { x: 0, y: 0 };
+        let window = geometry.client_to_window(client);
+        assert_eq!(window, WindowPoint { x: 8, y: 31 });
+        assert_eq!(geometry.window_to_client(window), client);
+        assert_eq!(
+            geometry.window_to_screen(window),
+            geometry.client_to_screen(client)
+        );
+    }
+
+    #[test]
+    fn fullscreen_at_origin_is_identity() {
+        let rect = Rect {
+            left: 0,
+            top: 0,
+            right: 1920,
+            bottom: 1080,
+        };
+        let geometry = WindowGeometry {
+            window: rect,
+            client: rect,
+            dpi: DEFAULT_DPI,
+            caller_dpi_aware: false,
+            window_dpi_aware: false,
+        };
+        let client = ClientPoint { x: 84, y: 46 };
+        assert_eq!(
+            geometry.client_to_screen(client),
+            ScreenPoint { x: 84, y: 46 }
+        );
+        assert_eq!(geometry.client_to_message(client), client);
+    }
+
+    #[test]
+    fn messages_are_scaled_when_awareness_differs() {
+        let mut geometry = windowed();
+        let point = ClientPoint { x: 1000, y: 600 };
+        assert_eq!(geometry.client_to_message(point), point);
+
+        geometry.caller_dpi_aware = false;
+        assert_eq!(
+            geometry.client_to_message(point),
+            ClientPoint { x: 1500, y: 900 }
+        );
+
+        geometry.caller_dpi_aware = true;
+        geometry.window_dpi_aware = false;
+        assert_eq!(
+            geometry.client_to_message(point),
+            ClientPoint { x: 667, y: 400 }
+        );
+    }
+
+    #[test]
+    fn dpi_conversion_rounds_like_muldiv() {
+        assert_eq!(geometry_scale(144), 1.5);
+        assert_eq!(logical_to_physical(101, 120), 126);
+        assert_eq!(physical_to_logical(126, 120), 101);
+        assert_eq!(logical_to_physical(-3, 144), -5);
+    }
+
+    fn geometry_scale(dpi: u32) -> f64 {
+        WindowGeometry { dpi, ..windowed() }.scale_factor()
+    }
+}
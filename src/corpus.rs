@@ -0,0 +1,251 @@
+//! Draws the synthetic frames in `tests/corpus` and their `expected.txt`.
+//!
+//! The frames mock the game UI from the 1920x1080 positions it was measured
+//! at, placed the way the game places it on other window sizes: scaled with
+//! the shorter side and kept at the edge or centre of the window it belongs to.
+//! That placement is written out here on its own rather than taken from
+//! `Layout`, so a mistake in one shows up against the other.
+//!
+//! When the game UI moves, update the positions here as well as in the
+//! profile, regenerate with `cargo test -- --ignored regenerate_corpus` and
+//! check the diff of the frames.
+
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    ColorType, ImageEncoder, Rgb, RgbImage,
+};
+use std::{fmt::Write, fs, path::Path};
+
+type Color = [u8; 3];
+
+const ICON: Color = [236, 229, 216];
+const BUBBLE: Color = [255, 255, 255];
+const BUBBLE_TEXT: Color = [60, 60, 60];
+
+/// Where on the window a UI element stays when the aspect ratio changes.
+#[derive(Clone, Copy)]
+enum Place {
+    TopLeft,
+    BottomCenter,
+    /// The game world, stretched over the whole window.
+    Stretch,
+}
+
+struct Frame {
+    image: RgbImage,
+    width: u32,
+    height: u32,
+}
+
+impl Frame {
+    fn new(width: u32, height: u32, color: Color) -> Self {
+        Self {
+            image: RgbImage::from_pixel(width, height, Rgb(color)),
+            width,
+            height,
+        }
+    }
+
+    /// Maps a 1920x1080 coordinate onto the window.
+    fn map(&self, x: u32, y: u32, place: Place) -> (f64, f64) {
+        let (width, height) = (f64::from(self.width), f64::from(self.height));
+        let (x, y) = (f64::from(x), f64::from(y));
+        let scale = (width / 1920.0).min(height / 1080.0);
+        match place {
+            Place::TopLeft => (x * scale, y * scale),
+            Place::BottomCenter => (
+                width / 2.0 + (x - 960.0) * scale,
+                height - (1080.0 - y) * scale,
+            ),
+            Place::Stretch => (x * width / 1920.0, y * height / 1080.0),
+        }
+    }
+
+    /// Fills `[x0, x1) x [y0, y1)` in reference coordinates, growing to whole pixels.
+    fn rect(&mut self, (x0, y0): (u32, u32), (x1, y1): (u32, u32), place: Place, color: Color) {
+        let (left, top) = self.map(x0, y0, place);
+        let (right, bottom) = self.map(x1, y1, place);
+        let columns = (left.floor() as u32)..(right.ceil() as u32).min(self.width);
+        for y in (top.floor() as u32)..(bottom.ceil() as u32).min(self.height) {
+            for x in columns.clone() {
+                self.image.put_pixel(x, y, Rgb(color));
+            }
+        }
+    }
+
+    /// A vertical gradient over the whole width, rows `[y0, y1)` in reference coordinates.
+    fn gradient(&mut self, y0: u32, y1: u32, from: Color, to: Color) {
+        let (_, top) = self.map(0, y0, Place::Stretch);
+        let (_, bottom) = self.map(0, y1, Place::Stretch);
+        let (top, bottom) = (top.floor() as u32, (bottom.ceil() as u32).min(self.height));
+        for y in top..bottom {
+            let t = f64::from(y - top) / f64::from((bottom - top).max(1));
+            let color = [0, 1, 2]
+                .map(|i| (f64::from(from[i]) + (f64::from(to[i]) - f64::from(from[i])) * t) as u8);
+            for x in 0..self.width {
+                self.image.put_pixel(x, y, Rgb(color));
+            }
+        }
+    }
+
+    fn save(&self, path: &Path) {
+        let file = fs::File::create(path).unwrap();
+        PngEncoder::new_with_quality(file, CompressionType::Best, FilterType::Adaptive)
+            .write_image(
+                self.image.as_raw(),
+                self.width,
+                self.height,
+                ColorType::Rgb8,
+            )
+            .unwrap();
+    }
+}
+
+/// A character close-up with the text box at the bottom.
+fn dialogue_scene(width: u32, height: u32) -> Frame {
+    let mut frame = Frame::new(width, height, [58, 66, 84]);
+    frame.gradient(0, 620, [92, 104, 128], [58, 66, 84]);
+    frame.rect((700, 180), (1220, 900), Place::Stretch, [176, 140, 122]);
+    frame.rect((0, 900), (1920, 1080), Place::BottomCenter, [24, 26, 32]);
+    frame.rect((860, 930), (1060, 950), Place::BottomCenter, [236, 190, 90]);
+    frame.rect(
+        (560, 975),
+        (1360, 995),
+        Place::BottomCenter,
+        [230, 230, 230],
+    );
+    frame
+}
+
+fn bubble(frame: &mut Frame, top: u32, color: Color) {
+    frame.rect((1290, top), (1700, top + 17), Place::BottomCenter, color);
+    frame.rect(
+        (1340, top + 5),
+        (1560, top + 11),
+        Place::BottomCenter,
+        BUBBLE_TEXT,
+    );
+}
+
+fn playing_icon(width: u32, height: u32, color: Color) -> Frame {
+    let mut frame = dialogue_scene(width, height);
+    frame.rect((72, 34), (96, 58), Place::TopLeft, color);
+    frame
+}
+
+fn option_lower(width: u32, height: u32, color: Color) -> Frame {
+    let mut frame = dialogue_scene(width, height);
+    bubble(&mut frame, 800, color);
+    frame
+}
+
+fn option_higher(width: u32, height: u32) -> Frame {
+    let mut frame = dialogue_scene(width, height);
+    bubble(&mut frame, 781, BUBBLE);
+    bubble(&mut frame, 830, BUBBLE);
+    frame
+}
+
+fn loading_screen(width: u32, height: u32) -> Frame {
+    let mut frame = Frame::new(width, height, [255, 255, 255]);
+    frame.rect(
+        (860, 1000),
+        (1060, 1010),
+        Place::BottomCenter,
+        [200, 200, 200],
+    );
+    frame
+}
+
+/// A bright sky, close to the bubble white, over the minimap corner.
+fn open_world(width: u32, height: u32) -> Frame {
+    let mut frame = Frame::new(width, height, [88, 128, 70]);
+    frame.gradient(0, 560, [246, 249, 252], [122, 170, 222]);
+    frame.gradient(560, 1080, [104, 140, 82], [70, 104, 58]);
+    frame.rect((40, 30), (260, 250), Place::TopLeft, [42, 48, 60]);
+    frame
+}
+
+/// A menu with a highlighted entry in the colour of the playing icon.
+fn menu(width: u32, height: u32) -> Frame {
+    let mut frame = Frame::new(width, height, [22, 26, 36]);
+    frame.rect((0, 0), (1920, 110), Place::Stretch, [34, 40, 54]);
+    frame.rect((120, 40), (520, 72), Place::TopLeft, [255, 255, 255]);
+    frame.rect((120, 200), (700, 1000), Place::TopLeft, [46, 52, 68]);
+    frame.rect((160, 240), (640, 280), Place::TopLeft, ICON);
+    frame
+}
+
+type Scene = fn(u32, u32) -> Frame;
+
+/// (name, frame, dialog played, without option)
+const SCENES: [(&str, Scene, bool, bool); 6] = [
+    ("playing_icon", |w, h| playing_icon(w, h, ICON), true, true),
+    (
+        "option_lower",
+        |w, h| option_lower(w, h, BUBBLE),
+        true,
+        false,
+    ),
+    ("option_higher", option_higher, true, false),
+    ("loading_screen", loading_screen, false, false),
+    ("open_world", open_world, false, false),
+    ("menu", menu, false, false),
+];
+
+/// 16:9 at the usual sizes, then ultrawide and 16:10.
+const SIZES: [(u32, u32); 6] = [
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+    (2560, 1440),
+    (2560, 1080),
+    (1920, 1200),
+];
+
+/// Off-colour versions of the elements, within the profile tolerance.
+const TINTED: [(&str, Scene, bool, bool); 2] = [
+    (
+        "playing_icon_tinted",
+        |w, h| playing_icon(w, h, [240, 226, 219]),
+        true,
+        true,
+    ),
+    (
+        "option_lower_tinted",
+        |w, h| option_lower(w, h, [251, 253, 249]),
+        true,
+        false,
+    ),
+];
+
+#[test]
+#[ignore]
+fn regenerate_corpus() {
+    let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus"));
+    let yes_no = |verdict: bool| if verdict { "yes" } else { "no" };
+    let mut manifest = String::from(
+        "# Expected detection verdicts for the frames in this folder.\n\
+         # These frames are synthetic, drawn by src/corpus.rs from the 1920x1080\n\
+         # positions of the game UI. Screenshots of the game go in captures/.\n\
+         #\n\
+         # file                            dialog_played  without_option\n",
+    );
+
+    let frames = SCENES
+        .iter()
+        .flat_map(|scene| SIZES.iter().map(move |size| (scene, *size)))
+        .chain(TINTED.iter().map(|scene| (scene, (1920, 1080))));
+    for (&(name, draw, dialog, without_option), (width, height)) in frames {
+        let file = format!("{name}_{width}x{height}.png");
+        draw(width, height).save(&dir.join(&file));
+        writeln!(
+            manifest,
+            "{file:<33} {:<14} {}",
+            yes_no(dialog),
+            yes_no(without_option)
+        )
+        .unwrap();
+    }
+    fs::write(dir.join("expected.txt"), manifest).unwrap();
+}
@@ -0,0 +1,134 @@
+use crate::genshin::CutsceneControl;
+
+/// Skips cutscenes: clicks the skip control, then the confirmation it brings up.
+///
+/// A confirmation is only clicked right after clicking skip, so a look-alike
+/// dialog the player opened (quitting the game, say) is left alone. Skip is
+/// not clicked again until the confirmation had `RETRY_FRAMES` frames to show up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CutsceneSkip {
+    /// Frames seen since skip was clicked, `None` if no confirmation is expected.
+    since_skip: Option<u32>,
+}
+
+impl CutsceneSkip {
+    const RETRY_FRAMES: u32 = 20;
+
+    /// The control to click, given the one on screen.
+    pub fn on_frame(&mut self, seen: Option<CutsceneControl>) -> Option<CutsceneControl> {
+        let since_skip = self.since_skip.map(|frames| frames + 1);
+        let (click, since_skip) = match (seen, since_skip) {
+            (Some(CutsceneControl::Confirm), Some(_)) => (Some(CutsceneControl::Confirm), None),
+            (Some(CutsceneControl::Skip), None) => (Some(CutsceneControl::Skip), Some(0)),
+            (Some(CutsceneControl::Skip), Some(frames)) if frames >= Self::RETRY_FRAMES => {
+                (Some(CutsceneControl::Skip), Some(0))
+            }
+            (_, Some(frames)) if frames >= Self::RETRY_FRAMES => (None, None),
+            (_, since_skip) => (None, since_skip),
+        };
+        self.since_skip = since_skip;
+        click
+    }
+
+    /// Dialogue is back, whatever the cutscene was doing is over.
+    pub fn reset(&mut self) {
+        self.since_skip = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        genshin::{Reaction, WindowProps},
+        options::OptionChoice,
+        pixel_source::{FrameBuffer, Rgb},
+        profile::{GameProfile, CUTSCENE_EXAMPLE},
+        strategy::{SkipStrategy, Strategy},
+    };
+    use CutsceneControl::*;
+
+    const BLACK: Rgb = (0, 0, 0);
+    const BUTTON: Rgb = (236, 229, 216);
+
+    #[test]
+    fn confirms_only_after_clicking_skip() {
+        let mut skip = CutsceneSkip::default();
+        // (on screen, clicked)
+        #[rustfmt::skip]
+        let script = [
+            (Some(Confirm), None),
+            (None, None),
+            (Some(Skip), Some(Skip)),
+            // The dialog takes a few frames to fade in
+            (Some(Skip), None),
+            (None, None),
+            (Some(Confirm), Some(Confirm)),
+            (Some(Confirm), None),
+            (Some(Skip), Some(Skip)),
+        ];
+        for (i, (seen, clicked)) in script.into_iter().enumerate() {
+            assert_eq!(skip.on_frame(seen), clicked, "frame {i}");
+        }
+    }
+
+    #[test]
+    fn retries_skip_when_nothing_happens() {
+        let mut skip = CutsceneSkip::default();
+        assert_eq!(skip.on_frame(Some(Skip)), Some(Skip));
+        let clicks = (0..CutsceneSkip::RETRY_FRAMES)
+            .filter(|_| skip.on_frame(Some(Skip)).is_some())
+            .count();
+        assert_eq!(clicks, 1);
+
+        // Long after the click a dialog is not ours any more
+        for _ in 0..CutsceneSkip::RETRY_FRAMES {
+            skip.on_frame(None);
+        }
+        assert_eq!(skip.on_frame(Some(Confirm)), None);
+    }
+
+    #[test]
+    fn skips_a_scripted_cutscene() {
+        let (overworld, props) = FrameBuffer::screen(&GameProfile::genshin_with(CUTSCENE_EXAMPLE));
+        let mut cutscene = overworld.clone();
+        cutscene.paint(&props, "letterbox", BLACK);
+        let mut with_skip = cutscene.clone();
+        with_skip.paint(&props, "skip_button", BUTTON);
+        let mut confirm = with_skip.clone();
+        confirm.paint(&props, "skip_confirm", BUTTON);
+        // The same button outside a cutscene
+        let mut menu = overworld.clone();
+        menu.paint(&props, "skip_button", BUTTON);
+        menu.paint(&props, "skip_confirm", BUTTON);
+
+        #[rustfmt::skip]
+        let script = [
+            (&menu, Reaction::Wait),
+            (&cutscene, Reaction::Wait),
+            (&with_skip, Reaction::ClickCutscene(Skip)),
+            (&cutscene, Reaction::Wait),
+            (&confirm, Reaction::ClickCutscene(Confirm)),
+            (&overworld, Reaction::Wait),
+        ];
+        let mut strategy = Strategy::new(SkipStrategy::PressSpace, true);
+        for (i, (frame, expected)) in script.into_iter().enumerate() {
+            let got = strategy.react(frame, &props, OptionChoice::Last).unwrap();
+            assert_eq!(got, expected, "frame {i}");
+        }
+
+        let mut off = Strategy::new(SkipStrategy::PressSpace, false);
+        for frame in [&with_skip, &confirm] {
+            let got = off.react(frame, &props, OptionChoice::Last).unwrap();
+            assert_eq!(got, Reaction::Wait);
+        }
+
+        // A profile without the controls never clicks, even with skipping on
+        let builtin = WindowProps::from_size(1920, 1080, &GameProfile::default());
+        let mut strategy = Strategy::new(SkipStrategy::PressSpace, true);
+        for frame in [&with_skip, &confirm, &with_skip] {
+            let got = strategy.react(frame, &builtin, OptionChoice::Last).unwrap();
+            assert_eq!(got, Reaction::Wait);
+        }
+    }
+}
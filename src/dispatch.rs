@@ -0,0 +1,236 @@
+use crate::global_states::EventType;
+use color_eyre::{eyre::Context, Result};
+use once_cell::sync::OnceCell;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        mpsc::Sender,
+    },
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookErr {
+    #[error("The event channel was not set up")]
+    NoChannel,
+    #[error("The event channel is closed")]
+    ChannelClosed,
+    #[error("A hook callback panicked")]
+    Panicked,
+}
+
+impl HookErr {
+    const ALL: [HookErr; 3] = [
+        HookErr::NoChannel,
+        HookErr::ChannelClosed,
+        HookErr::Panicked,
+    ];
+
+    /// Zero is left for an empty slot.
+    fn code(self) -> u8 {
+        match self {
+            HookErr::NoChannel => 1,
+            HookErr::ChannelClosed => 2,
+            HookErr::Panicked => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|err| err.code() == code)
+    }
+}
+
+/// Where hook callbacks leave their failures. Written from inside the
+/// callbacks, so it only uses atomics: taking a lock there could block the
+/// whole input queue.
+#[derive(Debug)]
+pub struct HookErrors {
+    first: AtomicU8,
+    count: AtomicUsize,
+}
+
+impl HookErrors {
+    pub const fn new() -> Self {
+        Self {
+            first: AtomicU8::new(0),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Keeps the first error and counts the rest. Returns whether it was the first.
+    fn record(&self, err: HookErr) -> bool {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        self.first
+            .compare_exchange(0, err.code(), Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// The first error recorded since the last call, and how many there were in total.
+    pub fn take(&self) -> Option<(HookErr, usize)> {
+        let first = HookErr::from_code(self.first.swap(0, Ordering::SeqCst));
+        let count = self.count.swap(0, Ordering::SeqCst);
+        first.map(|err| (err, count))
+    }
+
+    /// Turns whatever was recorded into an error for `App::run`.
+    pub fn check(&self) -> Result<()> {
+        match self.take() {
+            Some((err, count)) => Err(err).wrap_err(format!("{count} hook callback(s) failed")),
+            None => Ok(()),
+        }
+    }
+}
+
+pub static HOOK_ERRORS: HookErrors = HookErrors::new();
+
+/// Where the hooks send their events, the app channel or a fake in tests.
+pub trait EventSink {
+    fn send_event(&self, event: EventType) -> Result<(), HookErr>;
+}
+
+impl EventSink for OnceCell<Sender<EventType>> {
+    fn send_event(&self, event: EventType) -> Result<(), HookErr> {
+        self.get()
+            .ok_or(HookErr::NoChannel)?
+            .send(event)
+            .map_err(|_| HookErr::ChannelClosed)
+    }
+}
+
+/// Runs the body of a hook callback. Nothing may unwind out of an
+/// `extern "system"` function, so a panic is caught, recorded and reported
+/// with `EventType::HookFailed`, and `fallback` is returned instead.
+///
+/// `callback` gets a function to send events with, which returns whether the
+/// event got through. Failed sends are recorded too.
+pub fn guarded<S: EventSink, R>(
+    sink: &S,
+    errors: &HookErrors,
+    fallback: R,
+    callback: impl FnOnce(&dyn Fn(EventType) -> bool) -> R,
+) -> R {
+    let emit = |event| match sink.send_event(event) {
+        Ok(()) => true,
+        Err(err) => {
+            errors.record(err);
+            false
+        }
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| callback(&emit))) {
+        Ok(result) => result,
+        Err(_) => {
+            // Later panics are only counted, the main loop is already on its way out
+            if errors.record(HookErr::Panicked) {
+                let _ = sink.send_event(EventType::HookFailed);
+            }
+            fallback
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkeys::{KeyCombo, KeyEvent};
+    use std::{cell::RefCell, sync::mpsc::channel};
+
+    #[derive(Default)]
+    struct FakeChannel {
+        sent: RefCell<Vec<EventType>>,
+        closed: bool,
+    }
+
+    impl EventSink for FakeChannel {
+        fn send_event(&self, event: EventType) -> Result<(), HookErr> {
+            if self.closed {
+                return Err(HookErr::ChannelClosed);
+            }
+            self.sent.borrow_mut().push(event);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn delivers_events() {
+        let channel = FakeChannel::default();
+        let errors = HookErrors::new();
+
+        let f9 = "F9".parse::<KeyCombo>().unwrap();
+        let swallowed = guarded(&channel, &errors, false, |emit| {
+            emit(EventType::Key(KeyEvent::Pressed(f9))) && emit(EventType::Shutdown)
+        });
+        assert!(swallowed);
+        assert!(matches!(
+            channel.sent.borrow()[..],
+            [EventType::Key(KeyEvent::Pressed(combo)), EventType::Shutdown] if combo == f9
+        ));
+        assert_eq!(errors.take(), None);
+    }
+
+    #[test]
+    fn records_failed_sends() {
+        let channel = FakeChannel {
+            closed: true,
+            ..Default::default()
+        };
+        let errors = HookErrors::new();
+
+        for _ in 0..3 {
+            let handled = guarded(&channel, &errors, 0, |emit| {
+                i32::from(emit(EventType::Shutdown))
+            });
+            assert_eq!(handled, 0);
+        }
+        assert_eq!(errors.take(), Some((HookErr::ChannelClosed, 3)));
+        assert_eq!(errors.take(), None);
+    }
+
+    #[test]
+    fn catches_panics_and_reports_them_once() {
+        let channel = FakeChannel::default();
+        let errors = HookErrors::new();
+
+        for _ in 0..2 {
+            let swallowed = guarded(&channel, &errors, false, |emit| {
+                emit(EventType::Shutdown);
+                panic!("poisoned keyboard state")
+            });
+            assert!(!swallowed);
+        }
+        assert!(matches!(
+            channel.sent.borrow()[..],
+            [
+                EventType::Shutdown,
+                EventType::HookFailed,
+                EventType::Shutdown,
+            ]
+        ));
+
+        let err = errors.check().unwrap_err();
+        assert_eq!(err.to_string(), "2 hook callback(s) failed");
+        assert_eq!(err.downcast_ref::<HookErr>(), Some(&HookErr::Panicked));
+        assert!(errors.check().is_ok());
+    }
+
+    #[test]
+    fn sends_through_the_app_channel() {
+        let cell = OnceCell::new();
+        assert_eq!(
+            cell.send_event(EventType::Shutdown),
+            Err(HookErr::NoChannel)
+        );
+
+        let (sender, receiver) = channel();
+        cell.set(sender).unwrap();
+        assert_eq!(cell.send_event(EventType::Shutdown), Ok(()));
+        assert!(matches!(receiver.try_recv(), Ok(EventType::Shutdown)));
+
+        drop(receiver);
+        assert_eq!(
+            cell.send_event(EventType::Shutdown),
+            Err(HookErr::ChannelClosed)
+        );
+    }
+}
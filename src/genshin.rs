@@ -1,117 +1,398 @@
-use crate::winapi_bindings::{
-    bindings::{
-        find_window, get_color_pixel, get_foreground_window, get_window_size, send_message_click,
-        send_message_space, set_cursor_position,
-    },
-    utils::to_wide_string,
+use crate::{
+    color::ColorMatcher,
+    coords::Rect,
+    layout::{Anchor, Layout},
+    options::{OptionChoice, OptionScan},
+    pixel_source::{PixelSource, Rgb},
+    profile::GameProfile,
+    region::SampleRegion,
 };
 use color_eyre::Result;
-use once_cell::sync::OnceCell;
+use serde::Deserialize;
+#[cfg(windows)]
+use thiserror::Error;
+
+#[cfg(windows)]
+use crate::{
+    capture::{FrameGrabber, GdiFrameGrabber},
+    coords::{ClientPoint, WindowGeometry},
+    pixel_source::{FrameBuffer, PixelSourceErr},
+    profile::detect,
+    winapi_bindings::bindings::{
+        client_to_screen, enum_visible_windows, get_class_name, get_client_size,
+        get_dpi_for_window, get_foreground_window, get_process_image_path, get_window_process_id,
+        get_window_rect, get_window_title, is_thread_dpi_aware, is_window_dpi_aware,
+        is_window_visible, send_message_click, send_message_space, set_cursor_position,
+    },
+    window_match::{exe_name, WindowInfo},
+};
+#[cfg(windows)]
 use rand::Rng;
+#[cfg(windows)]
 use std::{thread::sleep, time::Duration};
+#[cfg(windows)]
 use winapi::shared::windef::HWND;
 
-static GENSHIN_WINDOW_NAME: OnceCell<Vec<u16>> = OnceCell::new();
-static GENSHIN_WINDOW_CLASS_NAME: OnceCell<Vec<u16>> = OnceCell::new();
+/// A profile probe placed on a window of a particular size.
+#[derive(Clone, Debug)]
+struct Probe {
+    name: String,
+    region: SampleRegion,
+    color: Rgb,
+    color_matcher: ColorMatcher,
+}
 
-#[derive(Clone, Copy, Debug)]
+impl Probe {
+    fn matches(&self, source: &impl PixelSource) -> Result<bool> {
+        self.region.matches(source, &self.color_matcher, self.color)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    /// Indexes into `WindowProps::probes`
+    probes: Vec<usize>,
+    action: DialogAction,
+}
+
+#[derive(Clone, Debug)]
 pub struct WindowProps {
     bottom_dialogue_min_x: i32,
     bottom_dialogue_max_x: i32,
     bottom_dialogue_min_y: i32,
     bottom_dialogue_max_y: i32,
-    playing_icon_x: i32,
-    playing_icon_y: i32,
-    dialogue_icon_x: i32,
-    dialogue_icon_lower_y: i32,
-    dialogue_icon_higher_y: i32,
-    loading_screen_x: i32,
-    loading_screen_y: i32,
+    probes: Vec<Probe>,
+    rules: Vec<Rule>,
+    options: Option<OptionScan>,
+    auto_play: Option<AutoPlayButton>,
+    cutscene: Option<[CutsceneButton; 2]>,
+}
+
+/// The auto-play toggle placed on a window.
+#[derive(Clone, Copy, Debug)]
+struct AutoPlayButton {
+    x: i32,
+    y: i32,
+    /// Index into `WindowProps::probes`
+    on: usize,
+}
+
+/// A cutscene control placed on a window, indexed by `CutsceneControl`.
+#[derive(Clone, Debug)]
+struct CutsceneButton {
+    /// Indexes into `WindowProps::probes`
+    probes: Vec<usize>,
+    x: i32,
+    y: i32,
 }
 
 impl WindowProps {
-    const DEFAULT_WIDTH: i32 = 1920;
-    const DEFAULT_HEIGHT: i32 = 1080;
-
-    pub fn new(window: &Window) -> Result<Self> {
-        let (w_width, w_height) = get_window_size(window.hwnd())?;
-
-        Ok(Self {
-            bottom_dialogue_min_x: Self::width_adjust(1300, w_width),
-            bottom_dialogue_max_x: Self::width_adjust(1700, w_width),
-            bottom_dialogue_min_y: Self::height_adjust(790, w_height),
-            bottom_dialogue_max_y: Self::height_adjust(800, w_height),
-            playing_icon_x: Self::width_adjust(84, w_width),
-            playing_icon_y: Self::height_adjust(46, w_height),
-            dialogue_icon_x: Self::width_adjust(1301, w_width),
-            dialogue_icon_lower_y: Self::height_adjust(808, w_height),
-            dialogue_icon_higher_y: Self::height_adjust(790, w_height),
-            loading_screen_x: Self::width_adjust(1200, w_width),
-            loading_screen_y: Self::height_adjust(700, w_height),
-        })
+    pub fn new(source: &impl PixelSource, profile: &GameProfile) -> Result<Self> {
+        let (w_width, w_height) = source.size()?;
+        Ok(Self::from_size(w_width, w_height, profile))
     }
 
-    fn width_adjust(width: i32, window_width: i32) -> i32 {
-        width * window_width / Self::DEFAULT_WIDTH
+    pub fn from_size(w_width: i32, w_height: i32, profile: &GameProfile) -> Self {
+        Self::from_layout(&Layout::new(w_width, w_height, profile.fit()), profile)
     }
 
-    fn height_adjust(height: i32, window_height: i32) -> i32 {
-        height * window_height / Self::DEFAULT_HEIGHT
+    /// Positions are measured on a 1920x1080 screenshot and moved with the
+    /// part of the screen the element is attached to.
+    pub fn from_layout(layout: &Layout, profile: &GameProfile) -> Self {
+        let click_area = profile.click_area();
+        let (bottom_dialogue_min_x, bottom_dialogue_min_y) =
+            layout.point(click_area.min.0, click_area.min.1, click_area.anchor);
+        let (bottom_dialogue_max_x, bottom_dialogue_max_y) =
+            layout.point(click_area.max.0, click_area.max.1, click_area.anchor);
+
+        let probes: Vec<Probe> = profile
+            .probes()
+            .map(|(name, probe)| Probe {
+                name: name.to_string(),
+                region: Self::region_adjust(
+                    layout,
+                    probe.position.0,
+                    probe.position.1,
+                    probe.radius,
+                    probe.anchor,
+                ),
+                color: probe.color,
+                color_matcher: profile.probe_tolerance(probe),
+            })
+            .collect();
+
+        // The profile has already checked that every rule names existing probes
+        let rules = profile
+            .rules()
+            .iter()
+            .map(|rule| Rule {
+                probes: rule
+                    .when
+                    .iter()
+                    .filter_map(|name| probes.iter().position(|p| &p.name == name))
+                    .collect(),
+                action: rule.action,
+            })
+            .collect();
+
+        let options = profile
+            .options()
+            .map(|(column, color_matcher)| OptionScan::new(layout, column, color_matcher));
+
+        // The profile has checked this probe exists too
+        let auto_play = profile.auto_play().and_then(|button| {
+            let (x, y) = layout.point(button.position.0, button.position.1, button.anchor);
+            let on = probes.iter().position(|p| p.name == button.on)?;
+            Some(AutoPlayButton { x, y, on })
+        });
+
+        let cutscene = profile.cutscene().map(|cutscene| {
+            [&cutscene.skip, &cutscene.confirm].map(|control| {
+                let (x, y) = layout.point(control.click.0, control.click.1, control.anchor);
+                CutsceneButton {
+                    probes: control
+                        .when
+                        .iter()
+                        .filter_map(|name| probes.iter().position(|p| &p.name == name))
+                        .collect(),
+                    x,
+                    y,
+                }
+            })
+        });
+
+        Self {
+            bottom_dialogue_min_x,
+            bottom_dialogue_max_x,
+            bottom_dialogue_min_y,
+            bottom_dialogue_max_y,
+            probes,
+            rules,
+            options,
+            auto_play,
+            cutscene,
+        }
+    }
+
+    /// Overrides the tolerance of every probe.
+    pub fn with_color_matcher(mut self, color_matcher: ColorMatcher) -> Self {
+        for probe in &mut self.probes {
+            probe.color_matcher = color_matcher;
+        }
+        self
+    }
+
+    /// Where a probe ended up on this window.
+    pub fn region(&self, probe: &str) -> Option<SampleRegion> {
+        self.probes
+            .iter()
+            .find(|p| p.name == probe)
+            .map(|p| p.region)
+    }
+
+    /// The parts of the window detection reads from: a small square per probe
+    /// and the one pixel wide option column. Empty if the profile looks at nothing.
+    pub fn capture_areas(&self) -> impl Iterator<Item = Rect> + '_ {
+        self.probes
+            .iter()
+            .map(|probe| probe.region.bounds())
+            .chain(self.options.map(|scan| scan.bounds()))
+    }
+
+    /// Heights of the visible option bubbles, top to bottom. Always empty if
+    /// the profile has no option column.
+    pub fn find_options(&self, source: &impl PixelSource) -> Result<Vec<i32>> {
+        match &self.options {
+            Some(scan) => scan.find(source),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn region_adjust(layout: &Layout, x: i32, y: i32, radius: i32, anchor: Anchor) -> SampleRegion {
+        let (x, y) = layout.point(x, y, anchor);
+        let radius = layout.scale(radius).max(1);
+        SampleRegion::new(x, y, radius, radius)
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Window {
-    hwnd: usize,
+/// What the message sender should do after looking at a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DialogAction {
+    Wait,
+    PressSpace,
+    ClickOption,
 }
 
-impl Window {
-    pub const DEFAULT_DURATION: Duration = Duration::from_millis(50);
+pub fn dialog_played(source: &impl PixelSource, props: &WindowProps) -> Result<bool> {
+    Ok(next_action(source, props)? != DialogAction::Wait)
+}
 
-    pub fn new() -> Result<Self> {
-        let window_name = GENSHIN_WINDOW_NAME.get_or_init(|| to_wide_string("Genshin Impact"));
-        let class_name = GENSHIN_WINDOW_CLASS_NAME.get_or_init(|| to_wide_string("UnityWndClass"));
+/// Whether the playing icon is up: a character is talking and there is
+/// nothing to pick. `false` for a profile without a `playing_icon` probe.
+pub fn is_dialog_without_option(source: &impl PixelSource, props: &WindowProps) -> Result<bool> {
+    match props.probes.iter().find(|p| p.name == "playing_icon") {
+        Some(probe) => probe.matches(source),
+        None => Ok(false),
+    }
+}
 
-        let hwnd = find_window(window_name, class_name)?;
-        Ok(Self {
-            hwnd: hwnd as usize,
-        })
+/// Runs the profile rules in order, the first one whose probes all match decides.
+pub fn next_action(source: &impl PixelSource, props: &WindowProps) -> Result<DialogAction> {
+    'rules: for rule in &props.rules {
+        for &probe in &rule.probes {
+            if !props.probes[probe].matches(source)? {
+                continue 'rules;
+            }
+        }
+        return Ok(rule.action);
     }
+    Ok(DialogAction::Wait)
+}
 
-    pub fn dialog_played(&self, props: &WindowProps) -> Result<bool> {
-        let hwnd = self.hwnd();
+/// Whether the game's own auto-play is running, `None` if the profile does
+/// not know where its toggle is.
+pub fn auto_play_on(source: &impl PixelSource, props: &WindowProps) -> Result<Option<bool>> {
+    props
+        .auto_play
+        .map(|button| props.probes[button.on].matches(source))
+        .transpose()
+}
 
-        let dialog_icon_color = get_color_pixel(hwnd, props.playing_icon_x, props.playing_icon_y)?;
-        if dialog_icon_color == (236, 229, 216) {
-            return Ok(true);
+/// The controls of a skippable cutscene.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CutsceneControl {
+    Skip,
+    Confirm,
+}
+
+impl CutsceneControl {
+    fn index(self) -> usize {
+        match self {
+            CutsceneControl::Skip => 0,
+            CutsceneControl::Confirm => 1,
         }
+    }
+}
 
-        let white_pixel = (255, 255, 255);
-        if get_color_pixel(hwnd, props.loading_screen_x, props.loading_screen_y)? == white_pixel {
-            return Ok(false);
+/// Which cutscene control is on screen, `None` if neither is or the profile
+/// does not know them. The confirmation goes first, the skip control may
+/// still show behind it.
+pub fn cutscene_control(
+    source: &impl PixelSource,
+    props: &WindowProps,
+) -> Result<Option<CutsceneControl>> {
+    let Some(buttons) = &props.cutscene else {
+        return Ok(None);
+    };
+    'controls: for control in [CutsceneControl::Confirm, CutsceneControl::Skip] {
+        for &probe in &buttons[control.index()].probes {
+            if !props.probes[probe].matches(source)? {
+                continue 'controls;
+            }
         }
+        return Ok(Some(control));
+    }
+    Ok(None)
+}
+
+/// `DialogAction` with the option to click worked out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reaction {
+    Wait,
+    PressSpace,
+    /// Click the option at this height, anywhere in the click area if `None`.
+    ClickOption(Option<i32>),
+    /// Options are up and the player wants to choose.
+    AwaitChoice,
+    /// Click the game's auto-play toggle.
+    ToggleAutoPlay,
+    ClickCutscene(CutsceneControl),
+}
 
-        if get_color_pixel(hwnd, props.dialogue_icon_x, props.dialogue_icon_lower_y)? == white_pixel
-        {
-            return Ok(true);
+/// Decides what to do about a frame, picking among the options with `choice`.
+///
+/// If the rules ask for an option but none is found in the option column,
+/// the click goes to the click area as before. `Pause` waits for the player
+/// whether or not the options were found.
+pub fn react(
+    source: &impl PixelSource,
+    props: &WindowProps,
+    choice: OptionChoice,
+) -> Result<Reaction> {
+    Ok(match next_action(source, props)? {
+        DialogAction::Wait => Reaction::Wait,
+        DialogAction::PressSpace => Reaction::PressSpace,
+        // No need to look for the options if the player picks anyway
+        DialogAction::ClickOption if choice == OptionChoice::Pause => Reaction::AwaitChoice,
+        DialogAction::ClickOption => {
+            Reaction::ClickOption(choice.pick(&props.find_options(source)?))
         }
+    })
+}
+
+#[cfg(windows)]
+#[derive(Error, Debug)]
+pub enum WindowErr {
+    #[error("No window matches the game profiles")]
+    NotFound,
+}
+
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    hwnd: usize,
+}
 
-        if get_color_pixel(hwnd, props.dialogue_icon_x, props.dialogue_icon_higher_y)?
-            == white_pixel
-        {
-            return Ok(true);
+#[cfg(windows)]
+impl Window {
+    pub const DEFAULT_DURATION: Duration = Duration::from_millis(50);
+
+    /// The first visible top-level window of any of the games, with the profile
+    /// that found it. The `known` window found last time is kept while it is
+    /// still there, the others are only looked through when it is gone.
+    pub fn find(profiles: &[GameProfile], known: Option<Self>) -> Result<(Self, &GameProfile)> {
+        if let Some(known) = known.filter(|known| is_window_visible(known.hwnd())) {
+            if let Some((profile, _)) = detect(profiles, &[Self::info(known.hwnd())], &Self::exe) {
+                return Ok((known, profile));
+            }
         }
+        let windows: Vec<_> = enum_visible_windows()?
+            .into_iter()
+            .map(Self::info)
+            .collect();
+        let (profile, found) = detect(profiles, &windows, &Self::exe).ok_or(WindowErr::NotFound)?;
+        Ok((Self { hwnd: found.hwnd }, profile))
+    }
 
-        Ok(false)
+    fn info(hwnd: HWND) -> WindowInfo {
+        WindowInfo::new(hwnd as usize, get_window_title(hwnd), get_class_name(hwnd))
     }
 
-    pub fn is_dialog_without_option(&self, props: &WindowProps) -> Result<bool> {
-        let dialog_icon_color =
-            get_color_pixel(self.hwnd(), props.playing_icon_x, props.playing_icon_y)?;
-        if dialog_icon_color == (236, 229, 216) {
-            return Ok(true);
-        }
-        Ok(false)
+    /// Opens the process of the window, so only asked for once its title and
+    /// class match.
+    fn exe(hwnd: usize) -> Option<String> {
+        get_window_process_id(hwnd as HWND)
+            .and_then(get_process_image_path)
+            .map(|path| exe_name(&path).to_string())
+    }
+
+    /// Size of the client area, which is what the props are laid out in.
+    pub fn client_size(&self) -> Result<(i32, i32)> {
+        Ok(get_client_size(self.hwnd()).map_err(PixelSourceErr::Unreadable)?)
+    }
+
+    /// Captures the parts of the window the props read from into `frame`,
+    /// with a single read of the pixels.
+    pub fn grab_frame(
+        &self,
+        props: &WindowProps,
+        grabber: &mut GdiFrameGrabber,
+        frame: &mut FrameBuffer,
+    ) -> Result<()> {
+        grabber.set_window(self.hwnd());
+        grabber.set_areas(props.capture_areas());
+        grabber.grab(frame)
     }
 
     pub fn click_space(&self) -> Result<()> {
@@ -122,18 +403,81 @@ impl Window {
         Ok(())
     }
 
-    pub fn click_left_m_button_random_pos(&self, props: &WindowProps) -> Result<()> {
-        let hwnd = self.hwnd();
+    /// Clicks the option at height `y`, or somewhere in the click area. The
+    /// horizontal position is random within the click area either way.
+    pub fn click_option(&self, props: &WindowProps, y: Option<i32>) -> Result<()> {
         let mut rnd = rand::thread_rng();
         let pos_x = rnd.gen_range(props.bottom_dialogue_min_x..=props.bottom_dialogue_max_x);
-        let pos_y = rnd.gen_range(props.bottom_dialogue_min_y..=props.bottom_dialogue_max_y);
-        set_cursor_position(pos_x, pos_y)?;
-        send_message_click(hwnd, true, pos_x, pos_y)?;
+        let pos_y = y.unwrap_or_else(|| {
+            rnd.gen_range(props.bottom_dialogue_min_y..=props.bottom_dialogue_max_y)
+        });
+        self.click(ClientPoint { x: pos_x, y: pos_y })
+    }
+
+    /// Does nothing if the profile does not know where the toggle is.
+    pub fn click_auto_play(&self, props: &WindowProps) -> Result<()> {
+        match props.auto_play {
+            Some(button) => self.click(ClientPoint {
+                x: button.x,
+                y: button.y,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Does nothing if the profile does not know the cutscene controls.
+    pub fn click_cutscene(&self, props: &WindowProps, control: CutsceneControl) -> Result<()> {
+        match &props.cutscene {
+            Some(buttons) => {
+                let button = &buttons[control.index()];
+                self.click(ClientPoint {
+                    x: button.x,
+                    y: button.y,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn click(&self, client: ClientPoint) -> Result<()> {
+        let hwnd = self.hwnd();
+        // The props are in client coordinates, the cursor lives in screen coordinates
+        let geometry = self.geometry()?;
+        let screen = geometry.client_to_screen(client);
+        let message = geometry.client_to_message(client);
+
+        set_cursor_position(screen.x, screen.y)?;
+        send_message_click(hwnd, true, message.x, message.y)?;
         sleep(Self::DEFAULT_DURATION);
-        send_message_click(hwnd, false, pos_x, pos_y)?;
+        send_message_click(hwnd, false, message.x, message.y)?;
         Ok(())
     }
 
+    pub fn geometry(&self) -> Result<WindowGeometry> {
+        let hwnd = self.hwnd();
+        let (left, top, right, bottom) = get_window_rect(hwnd)?;
+        let (client_left, client_top) = client_to_screen(hwnd, 0, 0)?;
+        let (client_width, client_height) = get_client_size(hwnd)?;
+
+        Ok(WindowGeometry {
+            window: Rect {
+                left,
+                top,
+                right,
+                bottom,
+            },
+            client: Rect {
+                left: client_left,
+                top: client_top,
+                right: client_left + client_width,
+                bottom: client_top + client_height,
+            },
+            dpi: get_dpi_for_window(hwnd)?,
+            caller_dpi_aware: is_thread_dpi_aware(),
+            window_dpi_aware: is_window_dpi_aware(hwnd),
+        })
+    }
+
     pub fn is_active(&self) -> bool {
         if let Some(fg_w) = get_foreground_window() {
             self.hwnd() == fg_w
@@ -142,7 +486,163 @@ impl Window {
         }
     }
 
+    /// The window handle as a number, to compare with WinEvents.
+    pub fn id(&self) -> usize {
+        self.hwnd
+    }
+
+    pub fn process_id(&self) -> Option<u32> {
+        get_window_process_id(self.hwnd())
+    }
+
     fn hwnd(&self) -> HWND {
         self.hwnd as HWND
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pixel_source::FrameBuffer, profile::BUILTIN_PROFILES};
+
+    const BACKGROUND: Rgb = FrameBuffer::BACKGROUND;
+    const PLAYING_ICON_COLOR: Rgb = (236, 229, 216);
+    const WHITE_PIXEL: Rgb = (255, 255, 255);
+
+    fn frame() -> (FrameBuffer, WindowProps) {
+        FrameBuffer::screen(&GameProfile::default())
+    }
+
+    fn region(props: &WindowProps, probe: &str) -> SampleRegion {
+        props.region(probe).unwrap()
+    }
+
+    #[test]
+    fn empty_frame_waits() {
+        let (frame, props) = frame();
+        assert!(!dialog_played(&frame, &props).unwrap());
+        assert_eq!(next_action(&frame, &props).unwrap(), DialogAction::Wait);
+    }
+
+    #[test]
+    fn playing_icon_presses_space() {
+        let (mut frame, props) = frame();
+        frame.paint(&props, "playing_icon", PLAYING_ICON_COLOR);
+        assert!(is_dialog_without_option(&frame, &props).unwrap());
+        assert_eq!(
+            next_action(&frame, &props).unwrap(),
+            DialogAction::PressSpace
+        );
+    }
+
+    #[test]
+    fn playing_icon_is_read_whatever_the_rules_say() {
+        let text = BUILTIN_PROFILES[0].text.replace(
+            "when = [\"playing_icon\"]\naction = \"press-space\"",
+            "when = [\"playing_icon\"]\naction = \"wait\"",
+        );
+        let (mut frame, props) = FrameBuffer::screen(&GameProfile::parse(&text).unwrap());
+        frame.paint(&props, "playing_icon", PLAYING_ICON_COLOR);
+        assert_eq!(next_action(&frame, &props).unwrap(), DialogAction::Wait);
+        assert!(is_dialog_without_option(&frame, &props).unwrap());
+    }
+
+    #[test]
+    fn option_bubble_clicks() {
+        let props = props_of_1080p();
+        for region in [
+            region(&props, "dialogue_icon_lower"),
+            region(&props, "dialogue_icon_higher"),
+        ] {
+            let (mut frame, props) = frame();
+            frame.paint_region(&region, WHITE_PIXEL);
+            assert_eq!(
+                next_action(&frame, &props).unwrap(),
+                DialogAction::ClickOption
+            );
+        }
+    }
+
+    #[test]
+    fn loading_screen_is_not_a_dialog() {
+        let (mut frame, props) = frame();
+        frame.fill_rect(0, 0, 1920, 1080, WHITE_PIXEL);
+        assert_eq!(next_action(&frame, &props).unwrap(), DialogAction::Wait);
+    }
+
+    #[test]
+    fn stray_pixels_do_not_flip_the_verdict() {
+        let (mut frame, props) = frame();
+        let bubble = region(&props, "dialogue_icon_lower");
+        frame.set_pixel(bubble.x, bubble.y, WHITE_PIXEL).unwrap();
+        assert_eq!(next_action(&frame, &props).unwrap(), DialogAction::Wait);
+
+        let icon = region(&props, "playing_icon");
+        frame.paint_region(&icon, PLAYING_ICON_COLOR);
+        frame.set_pixel(icon.x, icon.y, (255, 180, 60)).unwrap();
+        assert_eq!(
+            next_action(&frame, &props).unwrap(),
+            DialogAction::PressSpace
+        );
+    }
+
+    #[test]
+    fn tolerance_accepts_tinted_playing_icon() {
+        let (mut frame, props) = frame();
+        frame.paint(&props, "playing_icon", (240, 226, 219));
+        assert_eq!(
+            next_action(&frame, &props).unwrap(),
+            DialogAction::PressSpace
+        );
+
+        let exact = props.with_color_matcher(ColorMatcher::Exact);
+        assert_eq!(next_action(&frame, &exact).unwrap(), DialogAction::Wait);
+    }
+
+    #[test]
+    fn near_white_sky_is_not_an_option_bubble() {
+        let (mut frame, props) = frame();
+        frame.fill_rect(0, 0, 1920, 1080, (246, 249, 252));
+        assert_eq!(next_action(&frame, &props).unwrap(), DialogAction::Wait);
+    }
+
+    #[test]
+    fn probes_stay_on_the_ui_for_any_aspect_ratio() {
+        #[rustfmt::skip]
+        let table = [
+            // (width, height, expected playing icon centre, expected lower bubble centre)
+            (1920, 1080, (84, 46), (1301, 808)),
+            (2560, 1080, (84, 46), (1621, 808)),
+            (3440, 1440, (112, 61), (2174, 1077)),
+            (5120, 1440, (112, 61), (3014, 1077)),
+            (1920, 1200, (84, 46), (1301, 928)),
+            (2560, 1600, (112, 61), (1734, 1238)),
+        ];
+
+        for (width, height, icon, bubble) in table {
+            let props = WindowProps::from_size(width, height, &GameProfile::default());
+            let icon_region = region(&props, "playing_icon");
+            let bubble_region = region(&props, "dialogue_icon_lower");
+            assert_eq!((icon_region.x, icon_region.y), icon);
+            assert_eq!((bubble_region.x, bubble_region.y), bubble);
+
+            let mut frame = FrameBuffer::new(width, height, BACKGROUND);
+            frame.paint_region(&bubble_region, WHITE_PIXEL);
+            assert_eq!(
+                next_action(&frame, &props).unwrap(),
+                DialogAction::ClickOption,
+                "{width}x{height}"
+            );
+        }
+    }
+
+    #[test]
+    fn probe_outside_frame_is_an_error() {
+        let frame = FrameBuffer::new(10, 10, BACKGROUND);
+        assert!(next_action(&frame, &props_of_1080p()).is_err());
+    }
+
+    fn props_of_1080p() -> WindowProps {
+        WindowProps::from_size(1920, 1080, &GameProfile::default())
+    }
+}
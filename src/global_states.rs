@@ -1,12 +1,17 @@
+use crate::{hotkeys::KeyEvent, win_events::WinEvent};
 use once_cell::sync::OnceCell;
 use std::sync::mpsc::Sender;
 
 #[derive(Debug)]
 pub enum EventType {
-    KeyPress(u32),
-    DestroyWindow,
-    ChangeForegroundWindow,
+    Key(KeyEvent),
+    DestroyWindow(WinEvent),
+    ChangeForegroundWindow(WinEvent),
+    /// The game window moved or changed size.
+    LocationChange(WinEvent),
     Shutdown,
+    /// A hook callback failed, the details are in `HOOK_ERRORS`.
+    HookFailed,
 }
 
 pub static EVENT_LISTENER_CHANNEL: OnceCell<Sender<EventType>> = OnceCell::new();
@@ -0,0 +1,561 @@
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HotkeyErr {
+    #[error("Unknown key `{0}`")]
+    UnknownKey(String),
+    #[error("`{0}` has no key besides the modifiers")]
+    MissingKey(String),
+    #[error("{combo} is bound to both {first} and {second}")]
+    DuplicateBinding {
+        combo: KeyCombo,
+        first: HotkeyAction,
+        second: HotkeyAction,
+    },
+}
+
+/// Virtual-key codes, as reported by the low-level keyboard hook.
+mod vk {
+    pub const SHIFT: u32 = 0x10;
+    pub const CONTROL: u32 = 0x11;
+    pub const MENU: u32 = 0x12;
+    pub const LWIN: u32 = 0x5B;
+    pub const RWIN: u32 = 0x5C;
+    pub const LSHIFT: u32 = 0xA0;
+    pub const RSHIFT: u32 = 0xA1;
+    pub const LCONTROL: u32 = 0xA2;
+    pub const RCONTROL: u32 = 0xA3;
+    pub const LMENU: u32 = 0xA4;
+    pub const RMENU: u32 = 0xA5;
+    pub const F1: u32 = 0x70;
+    pub const F9: u32 = 0x78;
+    pub const F10: u32 = 0x79;
+    pub const F11: u32 = 0x7A;
+    pub const NUMPAD0: u32 = 0x60;
+}
+
+/// Named keys other than letters, digits and function keys.
+const NAMED_KEYS: [(&str, u32); 16] = [
+    ("Backspace", 0x08),
+    ("Tab", 0x09),
+    ("Enter", 0x0D),
+    ("Pause", 0x13),
+    ("Escape", 0x1B),
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("Insert", 0x2D),
+    ("Delete", 0x2E),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub win: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        win: false,
+    };
+}
+
+/// A key together with the modifiers that have to be held with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub key: u32,
+}
+
+impl KeyCombo {
+    pub const fn new(key: u32) -> Self {
+        Self {
+            modifiers: Modifiers::NONE,
+            key,
+        }
+    }
+
+    pub const fn with(modifiers: Modifiers, key: u32) -> Self {
+        Self { modifiers, key }
+    }
+}
+
+fn parse_key(name: &str) -> Option<u32> {
+    let upper = name.to_ascii_uppercase();
+    if let [c] = upper.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            return Some(u32::from(*c));
+        }
+    }
+    if let Some(number) = upper.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        if (1..=24).contains(&number) {
+            return Some(vk::F1 + number - 1);
+        }
+    }
+    if let Some(digit) = upper
+        .strip_prefix("NUMPAD")
+        .and_then(|n| n.parse::<u32>().ok())
+    {
+        if digit <= 9 {
+            return Some(vk::NUMPAD0 + digit);
+        }
+    }
+    let name = match upper.as_str() {
+        "ESC" => "ESCAPE",
+        "RETURN" => "ENTER",
+        "DEL" => "DELETE",
+        "INS" => "INSERT",
+        other => other,
+    };
+    NAMED_KEYS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code)
+}
+
+fn key_name(key: u32) -> String {
+    match key {
+        0x30..=0x39 | 0x41..=0x5A => char::from_u32(key).unwrap_or('?').to_string(),
+        k if (vk::F1..vk::F1 + 24).contains(&k) => format!("F{}", k - vk::F1 + 1),
+        k if (vk::NUMPAD0..=vk::NUMPAD0 + 9).contains(&k) => format!("Numpad{}", k - vk::NUMPAD0),
+        k => NAMED_KEYS
+            .iter()
+            .find(|(_, code)| *code == k)
+            .map(|(name, _)| name.to_string())
+            .unwrap_or_else(|| format!("Key{k:#04X}")),
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = HotkeyErr;
+
+    /// Modifiers and a key joined with `+`, such as `Ctrl+Alt+S` or `F9`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::NONE;
+        let mut key = None;
+        for part in s.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "win" | "super" => modifiers.win = true,
+                _ if key.is_none() => {
+                    key = Some(parse_key(part).ok_or_else(|| HotkeyErr::UnknownKey(part.into()))?)
+                }
+                _ => return Err(HotkeyErr::UnknownKey(s.to_string())),
+            }
+        }
+        let key = key.ok_or_else(|| HotkeyErr::MissingKey(s.to_string()))?;
+        Ok(KeyCombo::with(modifiers, key))
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = HotkeyErr;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Modifiers {
+            ctrl,
+            alt,
+            shift,
+            win,
+        } = self.modifiers;
+        for (held, name) in [(ctrl, "Ctrl"), (alt, "Alt"), (shift, "Shift"), (win, "Win")] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        f.write_str(&key_name(self.key))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEvent {
+    Pressed(KeyCombo),
+    Released(KeyCombo),
+}
+
+/// Which modifier keys are down, with left and right tracked apart so letting
+/// go of one does not clear the other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModifierState(u8);
+
+impl ModifierState {
+    const LEFT_CTRL: u8 = 1 << 0;
+    const RIGHT_CTRL: u8 = 1 << 1;
+    const LEFT_ALT: u8 = 1 << 2;
+    const RIGHT_ALT: u8 = 1 << 3;
+    const LEFT_SHIFT: u8 = 1 << 4;
+    const RIGHT_SHIFT: u8 = 1 << 5;
+    const LEFT_WIN: u8 = 1 << 6;
+    const RIGHT_WIN: u8 = 1 << 7;
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    fn flag(key: u32) -> Option<u8> {
+        match key {
+            vk::LCONTROL | vk::CONTROL => Some(Self::LEFT_CTRL),
+            vk::RCONTROL => Some(Self::RIGHT_CTRL),
+            vk::LMENU | vk::MENU => Some(Self::LEFT_ALT),
+            vk::RMENU => Some(Self::RIGHT_ALT),
+            vk::LSHIFT | vk::SHIFT => Some(Self::LEFT_SHIFT),
+            vk::RSHIFT => Some(Self::RIGHT_SHIFT),
+            vk::LWIN => Some(Self::LEFT_WIN),
+            vk::RWIN => Some(Self::RIGHT_WIN),
+            _ => None,
+        }
+    }
+
+    pub fn modifiers(self) -> Modifiers {
+        let any = |flags: u8| self.0 & flags != 0;
+        Modifiers {
+            ctrl: any(Self::LEFT_CTRL | Self::RIGHT_CTRL),
+            alt: any(Self::LEFT_ALT | Self::RIGHT_ALT),
+            shift: any(Self::LEFT_SHIFT | Self::RIGHT_SHIFT),
+            win: any(Self::LEFT_WIN | Self::RIGHT_WIN),
+        }
+    }
+
+    /// Feeds a key event in. Modifiers are only tracked, other keys come back
+    /// out together with the modifiers held at that moment.
+    pub fn update(&mut self, key: u32, pressed: bool) -> Option<KeyEvent> {
+        match Self::flag(key) {
+            Some(flag) if pressed => self.0 |= flag,
+            Some(flag) => self.0 &= !flag,
+            None => {
+                let combo = KeyCombo::with(self.modifiers(), key);
+                return Some(if pressed {
+                    KeyEvent::Pressed(combo)
+                } else {
+                    KeyEvent::Released(combo)
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Everything the low-level keyboard hook keeps between calls, and the
+/// decision of which keys stay away from the game.
+#[derive(Debug, Default)]
+pub struct KeyboardState {
+    modifiers: ModifierState,
+    swallowed: Vec<KeyCombo>,
+    /// Keys whose key-down was swallowed, so the matching key-up is as well
+    /// even if the modifiers were let go in between.
+    held: Vec<u32>,
+}
+
+impl KeyboardState {
+    pub const fn new() -> Self {
+        Self {
+            modifiers: ModifierState::from_bits(0),
+            swallowed: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn set_swallowed(&mut self, combos: Vec<KeyCombo>) {
+        self.swallowed = combos;
+        self.held.clear();
+    }
+
+    /// Returns the event to report, and whether to keep the key from the game.
+    pub fn process(&mut self, key: u32, pressed: bool) -> (Option<KeyEvent>, bool) {
+        let event = self.modifiers.update(key, pressed);
+        let swallow = match event {
+            Some(KeyEvent::Pressed(combo)) if self.swallowed.contains(&combo) => {
+                if !self.held.contains(&key) {
+                    self.held.push(key);
+                }
+                true
+            }
+            Some(KeyEvent::Released(_)) => match self.held.iter().position(|k| *k == key) {
+                Some(index) => {
+                    self.held.swap_remove(index);
+                    true
+                }
+                None => false,
+            },
+            // Modifiers always go through, the game would get stuck keys otherwise
+            _ => false,
+        };
+        (event, swallow)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Run,
+    Pause,
+    Exit,
+}
+
+impl fmt::Display for HotkeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HotkeyAction::Run => "Run",
+            HotkeyAction::Pause => "Pause",
+            HotkeyAction::Exit => "Exit",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeyBindings {
+    pub run: KeyCombo,
+    pub pause: KeyCombo,
+    pub exit: KeyCombo,
+}
+
+impl Default for HotkeyBindings {
+    fn default() -> Self {
+        Self {
+            run: KeyCombo::new(vk::F9),
+            pause: KeyCombo::new(vk::F10),
+            exit: KeyCombo::new(vk::F11),
+        }
+    }
+}
+
+impl HotkeyBindings {
+    /// Bindings in the order they are shown in the menu.
+    pub fn iter(&self) -> impl Iterator<Item = (HotkeyAction, KeyCombo)> {
+        [
+            (HotkeyAction::Run, self.run),
+            (HotkeyAction::Pause, self.pause),
+            (HotkeyAction::Exit, self.exit),
+        ]
+        .into_iter()
+    }
+
+    pub fn action_for(&self, combo: KeyCombo) -> Option<HotkeyAction> {
+        self.iter()
+            .find(|(_, bound)| *bound == combo)
+            .map(|(action, _)| action)
+    }
+
+    pub fn validate(&self) -> Result<(), HotkeyErr> {
+        let bindings: Vec<_> = self.iter().collect();
+        for (index, (first, combo)) in bindings.iter().enumerate() {
+            if let Some((second, _)) = bindings[index + 1..].iter().find(|(_, c)| c == combo) {
+                return Err(HotkeyErr::DuplicateBinding {
+                    combo: *combo,
+                    first: *first,
+                    second: *second,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTRL_ALT: Modifiers = Modifiers {
+        ctrl: true,
+        alt: true,
+        shift: false,
+        win: false,
+    };
+
+    #[test]
+    fn parses_and_prints_combinations() {
+        for (text, expected, printed) in [
+            ("F9", KeyCombo::new(vk::F9), "F9"),
+            ("ctrl+alt+s", KeyCombo::with(CTRL_ALT, 0x53), "Ctrl+Alt+S"),
+            (
+                "Alt + Control + 5",
+                KeyCombo::with(CTRL_ALT, 0x35),
+                "Ctrl+Alt+5",
+            ),
+            (
+                "Shift+PageDown",
+                KeyCombo::with(
+                    Modifiers {
+                        shift: true,
+                        ..Modifiers::NONE
+                    },
+                    0x22,
+                ),
+                "Shift+PageDown",
+            ),
+            (
+                "Win+Esc",
+                KeyCombo::with(
+                    Modifiers {
+                        win: true,
+                        ..Modifiers::NONE
+                    },
+                    0x1B,
+                ),
+                "Win+Escape",
+            ),
+            ("Numpad7", KeyCombo::new(0x67), "Numpad7"),
+        ] {
+            let combo: KeyCombo = text.parse().unwrap();
+            assert_eq!(combo, expected, "{text}");
+            assert_eq!(combo.to_string(), printed, "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_combinations() {
+        for text in ["", "Ctrl+Alt", "F25", "Ctrl+Hyper+S", "S+D"] {
+            assert!(text.parse::<KeyCombo>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn tracks_modifiers_across_key_events() {
+        let mut state = ModifierState::default();
+        assert_eq!(state.update(vk::LCONTROL, true), None);
+        assert_eq!(state.update(vk::RMENU, true), None);
+        assert_eq!(
+            state.update(0x53, true),
+            Some(KeyEvent::Pressed(KeyCombo::with(CTRL_ALT, 0x53)))
+        );
+        assert_eq!(
+            state.update(0x53, false),
+            Some(KeyEvent::Released(KeyCombo::with(CTRL_ALT, 0x53)))
+        );
+
+        state.update(vk::RMENU, false);
+        assert_eq!(
+            state.update(0x53, true),
+            Some(KeyEvent::Pressed(KeyCombo::with(
+                Modifiers {
+                    ctrl: true,
+                    ..Modifiers::NONE
+                },
+                0x53
+            )))
+        );
+    }
+
+    #[test]
+    fn releasing_one_side_keeps_the_other() {
+        let mut state = ModifierState::default();
+        state.update(vk::LSHIFT, true);
+        state.update(vk::RSHIFT, true);
+        state.update(vk::LSHIFT, false);
+        assert!(state.modifiers().shift);
+        state.update(vk::RSHIFT, false);
+        assert_eq!(state.modifiers(), Modifiers::NONE);
+    }
+
+    #[test]
+    fn swallows_bound_keys_down_and_up() {
+        let ctrl_s = KeyCombo::with(
+            Modifiers {
+                ctrl: true,
+                ..Modifiers::NONE
+            },
+            0x53,
+        );
+        let mut state = KeyboardState::new();
+        state.set_swallowed(vec![ctrl_s, KeyCombo::new(vk::F11)]);
+
+        // (key, pressed, swallowed)
+        let steps = [
+            (vk::LCONTROL, true, false),
+            (0x53, true, true),
+            // Key repeat
+            (0x53, true, true),
+            // Ctrl goes first, S still has to be hidden from the game
+            (vk::LCONTROL, false, false),
+            (0x53, false, true),
+            // Plain S is typing, not our hotkey
+            (0x53, true, false),
+            (0x53, false, false),
+            (vk::F11, true, true),
+            (vk::F11, false, true),
+            (vk::F10, true, false),
+            (vk::F10, false, false),
+        ];
+        for (step, (key, pressed, swallowed)) in steps.into_iter().enumerate() {
+            assert_eq!(state.process(key, pressed).1, swallowed, "step {step}");
+        }
+    }
+
+    #[test]
+    fn passes_everything_when_nothing_is_swallowed() {
+        let mut state = KeyboardState::new();
+        for key in [vk::F9, vk::F10, vk::F11] {
+            assert_eq!(
+                state.process(key, true),
+                (Some(KeyEvent::Pressed(KeyCombo::new(key))), false)
+            );
+            assert_eq!(
+                state.process(key, false),
+                (Some(KeyEvent::Released(KeyCombo::new(key))), false)
+            );
+        }
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let bindings = HotkeyBindings {
+            run: "Ctrl+Alt+S".parse().unwrap(),
+            ..HotkeyBindings::default()
+        };
+        assert_eq!(
+            bindings.action_for(KeyCombo::with(CTRL_ALT, 0x53)),
+            Some(HotkeyAction::Run)
+        );
+        assert_eq!(bindings.action_for(KeyCombo::new(0x53)), None);
+        assert_eq!(
+            bindings.action_for(KeyCombo::new(vk::F11)),
+            Some(HotkeyAction::Exit)
+        );
+        assert_eq!(
+            bindings.action_for(KeyCombo::with(
+                Modifiers {
+                    ctrl: true,
+                    ..Modifiers::NONE
+                },
+                vk::F11
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn the_same_combination_cannot_do_two_things() {
+        let bindings = HotkeyBindings {
+            exit: "F9".parse().unwrap(),
+            ..HotkeyBindings::default()
+        };
+        assert!(bindings.validate().is_err());
+        assert!(HotkeyBindings::default().validate().is_ok());
+    }
+}
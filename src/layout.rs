@@ -0,0 +1,290 @@
+use crate::coords::Rect;
+use serde::Deserialize;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LayoutErr {
+    #[error("Unknown anchor `{0}`, expected something like `top-left` or `bottom-center`")]
+    UnknownAnchor(String),
+}
+
+/// Which edge of the screen a UI element keeps its distance from when the
+/// aspect ratio differs from 16:9.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HorizontalAnchor {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerticalAnchor {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Anchor {
+    pub horizontal: HorizontalAnchor,
+    pub vertical: VerticalAnchor,
+}
+
+impl Anchor {
+    pub const TOP_LEFT: Anchor = Anchor::new(HorizontalAnchor::Left, VerticalAnchor::Top);
+    pub const CENTER: Anchor = Anchor::new(HorizontalAnchor::Center, VerticalAnchor::Center);
+    pub const BOTTOM_CENTER: Anchor = Anchor::new(HorizontalAnchor::Center, VerticalAnchor::Bottom);
+
+    pub const fn new(horizontal: HorizontalAnchor, vertical: VerticalAnchor) -> Self {
+        Self {
+            horizontal,
+            vertical,
+        }
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = LayoutErr;
+
+    /// `center` on its own, otherwise `<vertical>-<horizontal>` such as `bottom-center`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "center" {
+            return Ok(Anchor::CENTER);
+        }
+        let unknown = || LayoutErr::UnknownAnchor(s.to_string());
+        let (vertical, horizontal) = s.split_once('-').ok_or_else(unknown)?;
+        let vertical = match vertical {
+            "top" => VerticalAnchor::Top,
+            "center" => VerticalAnchor::Center,
+            "bottom" => VerticalAnchor::Bottom,
+            _ => return Err(unknown()),
+        };
+        let horizontal = match horizontal {
+            "left" => HorizontalAnchor::Left,
+            "center" => HorizontalAnchor::Center,
+            "right" => HorizontalAnchor::Right,
+            _ => return Err(unknown()),
+        };
+        Ok(Anchor::new(horizontal, vertical))
+    }
+}
+
+impl TryFrom<String> for Anchor {
+    type Error = LayoutErr;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// How the game fills a window that is not 16:9.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fit {
+    /// The picture fills the window and the UI sticks to the window edges.
+    #[default]
+    Fill,
+    /// A 16:9 picture is centred with black bars around it.
+    Letterbox,
+}
+
+/// Maps coordinates measured on a 1920x1080 screenshot onto a window of any size.
+///
+/// The UI is scaled uniformly by the smaller of the two axis ratios, so on 21:9
+/// and 32:9 it follows the height and on 16:10 it follows the width. The spare
+/// room on the other axis is where the anchors matter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// The area the UI is laid out in, in client coordinates.
+    area: Rect,
+    /// The scale is `scale_num / scale_den`, kept as a fraction so 16:9 windows
+    /// round exactly like a plain `x * width / 1920`.
+    scale_num: i32,
+    scale_den: i32,
+}
+
+impl Layout {
+    pub const REFERENCE_WIDTH: i32 = 1920;
+    pub const REFERENCE_HEIGHT: i32 = 1080;
+
+    pub fn new(width: i32, height: i32, fit: Fit) -> Self {
+        let wider_than_reference = i64::from(width) * i64::from(Self::REFERENCE_HEIGHT)
+            >= i64::from(height) * i64::from(Self::REFERENCE_WIDTH);
+        let (scale_num, scale_den) = if wider_than_reference {
+            (height, Self::REFERENCE_HEIGHT)
+        } else {
+            (width, Self::REFERENCE_WIDTH)
+        };
+
+        let area = match fit {
+            Fit::Fill => Rect {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            },
+            Fit::Letterbox => {
+                let content_width = Self::REFERENCE_WIDTH * scale_num / scale_den;
+                let content_height = Self::REFERENCE_HEIGHT * scale_num / scale_den;
+                let left = (width - content_width) / 2;
+                let top = (height - content_height) / 2;
+                Rect {
+                    left,
+                    top,
+                    right: left + content_width,
+                    bottom: top + content_height,
+                }
+            }
+        };
+
+        Self {
+            area,
+            scale_num,
+            scale_den,
+        }
+    }
+
+    /// Length on the reference screen scaled to this window.
+    pub fn scale(&self, length: i32) -> i32 {
+        (length * self.scale_num).div_euclid(self.scale_den)
+    }
+
+    pub fn x(&self, x: i32, anchor: HorizontalAnchor) -> i32 {
+        let area = &self.area;
+        if self.fills(area.width(), Self::REFERENCE_WIDTH) {
+            return area.left + self.scale(x);
+        }
+        match anchor {
+            HorizontalAnchor::Left => area.left + self.scale(x),
+            HorizontalAnchor::Center => {
+                area.left + area.width() / 2 + self.scale(x - Self::REFERENCE_WIDTH / 2)
+            }
+            HorizontalAnchor::Right => area.right - self.scale(Self::REFERENCE_WIDTH - x),
+        }
+    }
+
+    pub fn y(&self, y: i32, anchor: VerticalAnchor) -> i32 {
+        let area = &self.area;
+        if self.fills(area.height(), Self::REFERENCE_HEIGHT) {
+            return area.top + self.scale(y);
+        }
+        match anchor {
+            VerticalAnchor::Top => area.top + self.scale(y),
+            VerticalAnchor::Center => {
+                area.top + area.height() / 2 + self.scale(y - Self::REFERENCE_HEIGHT / 2)
+            }
+            VerticalAnchor::Bottom => area.bottom - self.scale(Self::REFERENCE_HEIGHT - y),
+        }
+    }
+
+    pub fn point(&self, x: i32, y: i32, anchor: Anchor) -> (i32, i32) {
+        (self.x(x, anchor.horizontal), self.y(y, anchor.vertical))
+    }
+
+    /// `true` if the scaled reference is exactly `length` long. With no spare
+    /// room every anchor lands on the same spot, and measuring from the start
+    /// keeps the rounding of a plain `x * width / 1920` that the first
+    /// profiles were made with.
+    fn fills(&self, length: i32, reference: i32) -> bool {
+        length * self.scale_den == reference * self.scale_num
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYING_ICON: (i32, i32) = (84, 46);
+    const DIALOGUE_ICON: (i32, i32) = (1301, 790);
+
+    #[test]
+    fn sixteen_by_nine_matches_linear_scaling() {
+        for (width, height) in [
+            (1280, 720),
+            (1600, 900),
+            (1920, 1080),
+            (2560, 1440),
+            (3840, 2160),
+        ] {
+            let layout = Layout::new(width, height, Fit::Fill);
+            for anchor in [Anchor::TOP_LEFT, Anchor::CENTER, Anchor::BOTTOM_CENTER] {
+                for (x, y) in [PLAYING_ICON, DIALOGUE_ICON, (1301, 808), (1200, 700)] {
+                    assert_eq!(
+                        layout.point(x, y, anchor),
+                        (x * width / 1920, y * height / 1080),
+                        "{width}x{height} {anchor:?} ({x}, {y})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn anchored_positions_for_common_resolutions() {
+        const TOP_RIGHT: Anchor = Anchor::new(HorizontalAnchor::Right, VerticalAnchor::Top);
+
+        // (width, height, fit, anchor, reference point, expected point)
+        #[rustfmt::skip]
+        let table = [
+            // 16:9
+            (1920, 1080, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (84, 46)),
+            (1920, 1080, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1301, 790)),
+            (2560, 1440, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1734, 1053)),
+            // 21:9, the UI follows the height and the spare width goes to the sides
+            (2560, 1080, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (84, 46)),
+            (2560, 1080, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1621, 790)),
+            (3440, 1440, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (112, 61)),
+            (3440, 1440, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (2174, 1053)),
+            (3440, 1440, Fit::Fill, Anchor::CENTER, (1200, 700), (2040, 933)),
+            // 32:9
+            (5120, 1440, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (112, 61)),
+            (5120, 1440, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (3014, 1053)),
+            (5120, 1440, Fit::Fill, TOP_RIGHT, (1840, 40), (5014, 53)),
+            // 16:10, the UI follows the width and the spare height goes up and down
+            (1920, 1200, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (84, 46)),
+            (1920, 1200, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1301, 910)),
+            (1920, 1200, Fit::Fill, Anchor::CENTER, (1200, 700), (1200, 760)),
+            (2560, 1600, Fit::Fill, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1734, 1214)),
+            (1280, 800, Fit::Fill, Anchor::TOP_LEFT, PLAYING_ICON, (56, 30)),
+            // Letterboxed 16:9 picture, only the offset of the bars matters
+            (2560, 1080, Fit::Letterbox, Anchor::TOP_LEFT, PLAYING_ICON, (404, 46)),
+            (2560, 1080, Fit::Letterbox, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1621, 790)),
+            (1920, 1200, Fit::Letterbox, Anchor::TOP_LEFT, PLAYING_ICON, (84, 106)),
+            (1920, 1200, Fit::Letterbox, Anchor::BOTTOM_CENTER, DIALOGUE_ICON, (1301, 850)),
+        ];
+
+        for (width, height, fit, anchor, (x, y), expected) in table {
+            let layout = Layout::new(width, height, fit);
+            assert_eq!(
+                layout.point(x, y, anchor),
+                expected,
+                "{width}x{height} {fit:?} {anchor:?} ({x}, {y})"
+            );
+        }
+    }
+
+    #[test]
+    fn anchors_parse_from_profile_names() {
+        assert_eq!("center".parse::<Anchor>().unwrap(), Anchor::CENTER);
+        assert_eq!("top-left".parse::<Anchor>().unwrap(), Anchor::TOP_LEFT);
+        assert_eq!(
+            "bottom-center".parse::<Anchor>().unwrap(),
+            Anchor::BOTTOM_CENTER
+        );
+        assert_eq!(
+            "center-right".parse::<Anchor>().unwrap(),
+            Anchor::new(HorizontalAnchor::Right, VerticalAnchor::Center)
+        );
+        assert!("left-top".parse::<Anchor>().is_err());
+        assert!("middle".parse::<Anchor>().is_err());
+    }
+
+    #[test]
+    fn lengths_follow_the_smaller_axis() {
+        assert_eq!(Layout::new(3440, 1440, Fit::Fill).scale(30), 40);
+        assert_eq!(Layout::new(1920, 1200, Fit::Fill).scale(30), 30);
+        assert_eq!(Layout::new(1280, 720, Fit::Fill).scale(3), 2);
+    }
+}
@@ -1,15 +1,80 @@
 #![allow(dead_code, unused_variables, clippy::let_unit_value)]
 
+mod activation;
+#[cfg(windows)]
 mod app;
+mod capture;
+mod cli;
+mod color;
+mod config;
+mod coords;
+#[cfg(test)]
+mod corpus;
+mod cutscene;
+mod dispatch;
 mod genshin;
 mod global_states;
+mod hotkeys;
+mod layout;
+mod options;
+mod pixel_source;
+mod profile;
+mod region;
+mod replay;
+mod shutdown;
+mod skipper;
+mod strategy;
+mod supervisor;
+mod win_events;
+#[cfg(windows)]
 mod winapi_bindings;
+mod window_match;
+mod worker;
 
+#[cfg(windows)]
 use app::App;
+use cli::{Cli, Command};
 use color_eyre::Result;
+use config::Config;
+use profile::{GameProfile, BUILTIN_PROFILES};
 
 fn main() -> Result<()> {
     color_eyre::install()?;
-    App::run()?;
+    let cli = Cli::from_args(std::env::args().skip(1))?;
+    // Without a choice the skipper looks for every game it has a checked profile for
+    let profiles = match (&cli.profile, &cli.game) {
+        (Some(path), _) => vec![GameProfile::load(path)?],
+        (None, Some(game)) => vec![GameProfile::builtin(game)?],
+        (None, None) => GameProfile::auto_detected(),
+    };
+
+    match cli.command {
+        Command::Run => {
+            let config = match &cli.config {
+                Some(path) => Config::load(path)?,
+                None => Config::default(),
+            };
+            config.check_profiles(&profiles)?;
+            run(profiles, config)?
+        }
+        // Screenshots do not say which game they are from, Genshin unless told otherwise
+        Command::Replay(paths) => replay::run(&paths, &profiles[0])?,
+        Command::DefaultProfile => {
+            let game = cli.game.as_deref().unwrap_or(BUILTIN_PROFILES[0].name);
+            print!("{}", GameProfile::builtin_text(game)?)
+        }
+    }
     Ok(())
 }
+
+#[cfg(windows)]
+fn run(profiles: Vec<GameProfile>, config: Config) -> Result<()> {
+    App::run(profiles, config)
+}
+
+#[cfg(not(windows))]
+fn run(profiles: Vec<GameProfile>, config: Config) -> Result<()> {
+    Err(color_eyre::eyre::eyre!(
+        "Skipping dialogues only works on Windows, use `replay` to check screenshots"
+    ))
+}
@@ -0,0 +1,307 @@
+use crate::{
+    color::ColorMatcher,
+    coords::Rect,
+    genshin::Reaction,
+    layout::Layout,
+    pixel_source::{PixelSource, Rgb},
+    profile::OptionColumnDef,
+};
+use color_eyre::Result;
+use serde::Deserialize;
+use std::{fmt, num::NonZeroUsize};
+
+/// The column option bubbles are looked for in, placed on a window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptionScan {
+    x: i32,
+    top: i32,
+    bottom: i32,
+    min_height: i32,
+    color: Rgb,
+    color_matcher: ColorMatcher,
+}
+
+impl OptionScan {
+    pub fn new(layout: &Layout, column: &OptionColumnDef, color_matcher: ColorMatcher) -> Self {
+        let (x, top) = layout.point(column.x, column.top, column.anchor);
+        let (_, bottom) = layout.point(column.x, column.bottom, column.anchor);
+        Self {
+            x,
+            top,
+            bottom,
+            min_height: layout.scale(column.min_height).max(1),
+            color: column.color,
+            color_matcher,
+        }
+    }
+
+    /// Every pixel the scan reads.
+    pub fn bounds(&self) -> Rect {
+        Rect {
+            left: self.x,
+            top: self.top,
+            right: self.x + 1,
+            bottom: self.bottom + 1,
+        }
+    }
+
+    /// Middle of every bubble in the column, top to bottom. A bubble is a run
+    /// of the bubble colour at least `min_height` pixels tall.
+    pub fn find(&self, source: &impl PixelSource) -> Result<Vec<i32>> {
+        let mut options = Vec::new();
+        let mut run_start = None;
+        for y in self.top..=self.bottom + 1 {
+            let in_bubble = y <= self.bottom
+                && self
+                    .color_matcher
+                    .matches(source.get_pixel(self.x, y)?, self.color);
+            match (in_bubble, run_start) {
+                (true, None) => run_start = Some(y),
+                (false, Some(start)) => {
+                    if y - start >= self.min_height {
+                        options.push((start + y - 1) / 2);
+                    }
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Which option the skipper picks when the dialogue asks for a choice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptionChoice {
+    /// The top one.
+    First,
+    /// The bottom one, where the skipper always clicked before options were detected.
+    #[default]
+    Last,
+    /// Counting from 1 at the top. The last option if there are fewer.
+    Index(NonZeroUsize),
+    /// Leave the choice to the player.
+    Pause,
+}
+
+impl OptionChoice {
+    /// Height of the option to click among these, top to bottom. `None` if
+    /// there are none to choose from or the player chooses.
+    pub fn pick(self, options: &[i32]) -> Option<i32> {
+        let last = options.len().checked_sub(1)?;
+        let index = match self {
+            OptionChoice::First => 0,
+            OptionChoice::Last => last,
+            OptionChoice::Index(index) => (index.get() - 1).min(last),
+            OptionChoice::Pause => return None,
+        };
+        Some(options[index])
+    }
+}
+
+/// Where the sender is in a conversation when the player makes the choices.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChoiceState {
+    /// Lines are skipped as usual.
+    #[default]
+    Reading,
+    /// Options are up, nothing is pressed until they are gone.
+    AwaitingChoice,
+}
+
+impl fmt::Display for ChoiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChoiceState::Reading => "Option chosen, skipping again",
+            ChoiceState::AwaitingChoice => "Waiting for you to pick an option",
+        })
+    }
+}
+
+/// Tracks the `ChoiceState` over the frames the sender looks at.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChoiceWait {
+    state: ChoiceState,
+}
+
+impl ChoiceWait {
+    pub fn state(&self) -> ChoiceState {
+        self.state
+    }
+
+    /// What the sender does about a frame it reacted to with `reaction`, with
+    /// `options_up` if bubbles are still on screen. Once the player is asked to
+    /// choose, nothing is pressed until the options are gone: space would pick
+    /// the highlighted one for them.
+    pub fn hold(&mut self, reaction: Reaction, options_up: bool) -> Reaction {
+        let awaiting = match self.state {
+            ChoiceState::Reading => reaction == Reaction::AwaitChoice,
+            ChoiceState::AwaitingChoice => reaction == Reaction::AwaitChoice || options_up,
+        };
+        if awaiting {
+            self.state = ChoiceState::AwaitingChoice;
+            Reaction::AwaitChoice
+        } else {
+            self.state = ChoiceState::Reading;
+            reaction
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        genshin::{react, WindowProps},
+        layout::Fit,
+        pixel_source::FrameBuffer,
+        profile::GameProfile,
+        strategy::{SkipStrategy, Strategy},
+    };
+
+    const BACKGROUND: Rgb = (40, 60, 80);
+    const WHITE: Rgb = (255, 255, 255);
+
+    /// A 1920x1080 frame with a 17 pixel tall bubble centred on each height,
+    /// as in the screenshot corpus.
+    fn frame_with_options(heights: &[i32]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(1920, 1080, BACKGROUND);
+        for &y in heights {
+            frame.fill_rect(1290, y - 8, 12, 17, WHITE);
+        }
+        frame
+    }
+
+    fn index(n: usize) -> OptionChoice {
+        OptionChoice::Index(NonZeroUsize::new(n).unwrap())
+    }
+
+    #[test]
+    fn finds_every_bubble_top_to_bottom() {
+        let props = WindowProps::from_size(1920, 1080, &GameProfile::default());
+        for heights in [vec![], vec![808], vec![640, 724, 808]] {
+            let frame = frame_with_options(&heights);
+            assert_eq!(props.find_options(&frame).unwrap(), heights);
+        }
+    }
+
+    #[test]
+    fn ignores_specks_and_scales_with_the_window() {
+        let mut frame = frame_with_options(&[808]);
+        // Two white pixels are not a bubble
+        frame.fill_rect(1290, 700, 12, 2, WHITE);
+        let props = WindowProps::from_size(1920, 1080, &GameProfile::default());
+        assert_eq!(props.find_options(&frame).unwrap(), [808]);
+
+        let layout = Layout::new(1280, 720, Fit::Fill);
+        let mut small = FrameBuffer::new(1280, 720, BACKGROUND);
+        let (x, y) = layout.point(1290, 800, crate::layout::Anchor::BOTTOM_CENTER);
+        small.fill_rect(x, y, 8, 11, WHITE);
+        let props = WindowProps::from_layout(&layout, &GameProfile::default());
+        assert_eq!(props.find_options(&small).unwrap(), [y + 5]);
+    }
+
+    #[test]
+    fn picks_by_policy() {
+        let options = [640, 724, 808];
+        #[rustfmt::skip]
+        let table = [
+            (OptionChoice::First, Some(640)),
+            (OptionChoice::Last, Some(808)),
+            (index(2), Some(724)),
+            (index(5), Some(808)),
+            (OptionChoice::Pause, None),
+        ];
+        for (choice, expected) in table {
+            assert_eq!(choice.pick(&options), expected, "{choice:?}");
+            assert_eq!(choice.pick(&[]), None, "{choice:?}");
+        }
+    }
+
+    #[test]
+    fn reacts_to_synthetic_frames() {
+        let profile = GameProfile::default();
+        let props = WindowProps::from_size(1920, 1080, &profile);
+        let two_options = frame_with_options(&[740, 808]);
+
+        assert_eq!(
+            react(&two_options, &props, OptionChoice::First).unwrap(),
+            Reaction::ClickOption(Some(740))
+        );
+        assert_eq!(
+            react(&two_options, &props, OptionChoice::Last).unwrap(),
+            Reaction::ClickOption(Some(808))
+        );
+        assert_eq!(
+            react(&two_options, &props, OptionChoice::Pause).unwrap(),
+            Reaction::AwaitChoice
+        );
+        assert_eq!(
+            react(&frame_with_options(&[]), &props, OptionChoice::First).unwrap(),
+            Reaction::Wait
+        );
+    }
+
+    #[test]
+    fn pauses_while_options_are_up() {
+        let profile = GameProfile::default();
+        let props = WindowProps::from_size(1920, 1080, &profile);
+        let with_icon = |heights: &[i32]| {
+            let mut frame = frame_with_options(heights);
+            frame.paint(&props, "playing_icon", (236, 229, 216));
+            frame
+        };
+        let dialogue = with_icon(&[]);
+        // The line is still drawn under the options, space would pick one
+        let both = with_icon(&[740, 808]);
+        let options = frame_with_options(&[740, 808]);
+        let nothing = frame_with_options(&[]);
+
+        // (frame, what the sender does, state after it)
+        #[rustfmt::skip]
+        let script = [
+            (&dialogue, Reaction::PressSpace, ChoiceState::Reading),
+            (&options, Reaction::AwaitChoice, ChoiceState::AwaitingChoice),
+            (&options, Reaction::AwaitChoice, ChoiceState::AwaitingChoice),
+            (&both, Reaction::AwaitChoice, ChoiceState::AwaitingChoice),
+            (&dialogue, Reaction::PressSpace, ChoiceState::Reading),
+            (&options, Reaction::AwaitChoice, ChoiceState::AwaitingChoice),
+            (&nothing, Reaction::Wait, ChoiceState::Reading),
+            (&both, Reaction::PressSpace, ChoiceState::Reading),
+        ];
+        let mut strategy = Strategy::new(SkipStrategy::PressSpace, false);
+        for (i, (frame, reaction, state)) in script.into_iter().enumerate() {
+            let got = strategy.react(frame, &props, OptionChoice::Pause).unwrap();
+            assert_eq!(got, reaction, "frame {i}");
+            assert_eq!(strategy.choice_state(), state, "frame {i}");
+        }
+    }
+
+    #[test]
+    fn other_choices_never_wait() {
+        let mut wait = ChoiceWait::default();
+        for reaction in [
+            Reaction::ClickOption(Some(808)),
+            Reaction::ClickOption(None),
+            Reaction::PressSpace,
+        ] {
+            assert_eq!(wait.hold(reaction, true), reaction);
+            assert_eq!(wait.state(), ChoiceState::Reading);
+        }
+    }
+
+    #[test]
+    fn reads_the_choice_from_toml() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            choice: OptionChoice,
+        }
+        let parse = |text: &str| toml::from_str::<Wrapper>(text).map(|w| w.choice);
+
+        assert_eq!(parse(r#"choice = "first""#).unwrap(), OptionChoice::First);
+        assert_eq!(parse(r#"choice = "pause""#).unwrap(), OptionChoice::Pause);
+        assert_eq!(parse("choice = { index = 2 }").unwrap(), index(2));
+        assert!(parse("choice = { index = 0 }").is_err());
+    }
+}
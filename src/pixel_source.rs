@@ -0,0 +1,281 @@
+use crate::coords::Rect;
+use color_eyre::Result;
+use std::io;
+use thiserror::Error;
+
+#[cfg(windows)]
+use crate::winapi_bindings::bindings::{get_client_size, get_color_pixel};
+#[cfg(test)]
+use crate::{genshin::WindowProps, profile::GameProfile, region::SampleRegion};
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Error, Debug)]
+pub enum PixelSourceErr {
+    #[error("Pixel ({x}, {y}) is outside the {width}x{height} frame")]
+    OutOfBounds {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    #[error("Expected {expected} pixels for a {width}x{height} frame, got {actual}")]
+    SizeMismatch {
+        width: i32,
+        height: i32,
+        expected: usize,
+        actual: usize,
+    },
+    /// The window is minimised, mid resolution change or behind the secure desktop.
+    #[error("Could not read the game window")]
+    Unreadable(#[source] io::Error),
+}
+
+/// Where the detection logic reads pixel colours from.
+pub trait PixelSource {
+    /// Colour of the pixel at window coordinates `(x, y)`.
+    fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb>;
+
+    /// Width and height of the area the coordinates refer to.
+    fn size(&self) -> Result<(i32, i32)>;
+}
+
+/// Reads pixels straight from the window with `GetPixel`.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug)]
+pub struct GdiPixelSource {
+    hwnd: usize,
+}
+
+#[cfg(windows)]
+impl GdiPixelSource {
+    pub fn new(hwnd: HWND) -> Self {
+        Self {
+            hwnd: hwnd as usize,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl PixelSource for GdiPixelSource {
+    fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb> {
+        get_color_pixel(self.hwnd as HWND, x, y)
+    }
+
+    fn size(&self) -> Result<(i32, i32)> {
+        Ok(get_client_size(self.hwnd as HWND).map_err(PixelSourceErr::Unreadable)?)
+    }
+}
+
+/// In-memory RGB frame, row-major.
+///
+/// A frame grabbed from parts of a window is still read in window coordinates,
+/// each part holds the pixels of its rectangle and nothing around it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameBuffer {
+    /// The smallest rectangle around every part.
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    /// Each part with the index of its first pixel. A whole frame is one part.
+    parts: Vec<(Rect, usize)>,
+    pixels: Vec<Rgb>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: i32, height: i32, fill: Rgb) -> Self {
+        let len = (width.max(0) * height.max(0)) as usize;
+        Self {
+            left: 0,
+            top: 0,
+            width,
+            height,
+            parts: vec![(Self::whole(width, height), 0)],
+            pixels: vec![fill; len],
+        }
+    }
+
+    fn whole(width: i32, height: i32) -> Rect {
+        Rect {
+            left: 0,
+            top: 0,
+            right: width,
+            bottom: height,
+        }
+    }
+
+    pub fn from_pixels(width: i32, height: i32, pixels: Vec<Rgb>) -> Result<Self> {
+        let expected = (width.max(0) * height.max(0)) as usize;
+        if pixels.len() != expected {
+            return Err(PixelSourceErr::SizeMismatch {
+                width,
+                height,
+                expected,
+                actual: pixels.len(),
+            }
+            .into());
+        }
+        Ok(Self {
+            left: 0,
+            top: 0,
+            width,
+            height,
+            parts: vec![(Self::whole(width, height), 0)],
+            pixels,
+        })
+    }
+
+    /// Replaces the contents with a top-down 32-bit BGRA bitmap of `areas`
+    /// stacked one under the other, each against the left edge of a bitmap as
+    /// wide as the widest, as `capture_window` returns it. The pixel storage
+    /// is reused.
+    pub fn fill_from_bgra(&mut self, areas: &[Rect], bgra: &[u8]) -> Result<()> {
+        let width = areas.iter().map(Rect::width).max().unwrap_or(0).max(0);
+        let height: i32 = areas.iter().map(|area| area.height().max(0)).sum();
+        let expected = (width * height) as usize;
+        if bgra.len() != expected * 4 {
+            return Err(PixelSourceErr::SizeMismatch {
+                width,
+                height,
+                expected,
+                actual: bgra.len() / 4,
+            }
+            .into());
+        }
+        let bounds = areas
+            .iter()
+            .copied()
+            .reduce(Rect::union)
+            .unwrap_or_default();
+        self.left = bounds.left;
+        self.top = bounds.top;
+        self.width = bounds.width();
+        self.height = bounds.height();
+        self.parts.clear();
+        self.pixels.clear();
+        let rows = bgra.chunks_exact((width * 4).max(1) as usize);
+        let mut rows = rows.map(|row| row.chunks_exact(4));
+        for &area in areas {
+            self.parts.push((area, self.pixels.len()));
+            for row in rows.by_ref().take(area.height().max(0) as usize) {
+                self.pixels.extend(
+                    row.take(area.width() as usize)
+                        .map(|px| (px[2], px[1], px[0])),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The smallest part of the window around everything the frame holds.
+    pub fn area(&self) -> Rect {
+        Rect {
+            left: self.left,
+            top: self.top,
+            right: self.left + self.width,
+            bottom: self.top + self.height,
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: Rgb) -> Result<()> {
+        let index = self.index(x, y)?;
+        self.pixels[index] = color;
+        Ok(())
+    }
+
+    /// Paints the rectangle `[x, x + width) x [y, y + height)`, clipped to the frame.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: Rgb) {
+        let rect = Rect {
+            left: x,
+            top: y,
+            right: x + width,
+            bottom: y + height,
+        };
+        for &(area, start) in &self.parts {
+            let clipped = rect.intersect(area);
+            for row in clipped.top..clipped.bottom {
+                for col in clipped.left..clipped.right {
+                    self.pixels[Self::offset(area, start, col, row)] = color;
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Result<usize> {
+        let part = self.parts.iter().find(|(area, _)| area.contains(x, y));
+        match part {
+            Some(&(area, start)) => Ok(Self::offset(area, start, x, y)),
+            None => Err(PixelSourceErr::OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            }
+            .into()),
+        }
+    }
+
+    fn offset(area: Rect, start: usize, x: i32, y: i32) -> usize {
+        start + ((y - area.top) * area.width() + x - area.left) as usize
+    }
+}
+
+#[cfg(test)]
+impl FrameBuffer {
+    /// What the test frames are drawn on, a colour no probe looks for.
+    pub const BACKGROUND: Rgb = (40, 60, 90);
+
+    /// An empty 1920x1080 window and the props of `profile` on it.
+    pub fn screen(profile: &GameProfile) -> (Self, WindowProps) {
+        let frame = Self::new(1920, 1080, Self::BACKGROUND);
+        let props = WindowProps::from_size(1920, 1080, profile);
+        (frame, props)
+    }
+
+    /// Fills every pixel the probe reads, which must be in the profile.
+    pub fn paint(&mut self, props: &WindowProps, probe: &str, color: Rgb) {
+        self.paint_region(&props.region(probe).unwrap(), color);
+    }
+
+    pub fn paint_region(&mut self, region: &SampleRegion, color: Rgb) {
+        let bounds = region.bounds();
+        self.fill_rect(
+            bounds.left,
+            bounds.top,
+            bounds.width(),
+            bounds.height(),
+            color,
+        );
+    }
+}
+
+impl PixelSource for FrameBuffer {
+    fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb> {
+        Ok(self.pixels[self.index(x, y)?])
+    }
+
+    fn size(&self) -> Result<(i32, i32)> {
+        Ok((self.width, self.height))
+    }
+}
+
+impl<T: PixelSource + ?Sized> PixelSource for &T {
+    fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb> {
+        (**self).get_pixel(x, y)
+    }
+
+    fn size(&self) -> Result<(i32, i32)> {
+        (**self).size()
+    }
+}
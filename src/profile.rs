@@ -0,0 +1,599 @@
+use crate::{
+    color::ColorMatcher,
+    genshin::DialogAction,
+    layout::{Anchor, Fit},
+    pixel_source::Rgb,
+    window_match::{find_match, WindowInfo, WindowMatcher},
+};
+use color_eyre::{eyre::Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinProfile {
+    /// What `--game` takes.
+    pub name: &'static str,
+    pub text: &'static str,
+    /// Looked for without `--game`. Only profiles checked against screenshots
+    /// are, a guessed one would press keys in a game nobody asked to skip.
+    pub auto_detect: bool,
+}
+
+/// The first one is the default.
+pub const BUILTIN_PROFILES: [BuiltinProfile; 2] = [
+    BuiltinProfile {
+        name: "genshin",
+        text: include_str!("../profiles/genshin.toml"),
+        auto_detect: true,
+    },
+    BuiltinProfile {
+        name: "star-rail",
+        text: include_str!("../profiles/star-rail.toml"),
+        auto_detect: false,
+    },
+];
+
+#[derive(Error, Debug)]
+pub enum ProfileErr {
+    #[error("Rule {rule} refers to the unknown probe `{probe}`")]
+    UnknownProbe { rule: usize, probe: String },
+    #[error("Rule {0} has no probes, it would match every frame")]
+    EmptyRule(usize),
+    #[error("Probe `{0}` needs a radius of at least 1")]
+    InvalidRadius(String),
+    #[error("The option column needs `top` above `bottom` and a `min_height` of at least 1")]
+    InvalidOptionColumn,
+    #[error("The auto-play toggle refers to the unknown probe `{0}`")]
+    UnknownAutoPlayProbe(String),
+    #[error("The cutscene {0} control needs at least one probe")]
+    EmptyCutsceneControl(&'static str),
+    #[error("The cutscene {control} control refers to the unknown probe `{probe}`")]
+    UnknownCutsceneProbe {
+        control: &'static str,
+        probe: String,
+    },
+    #[error("Unknown game `{0}`, the built-in ones are {}", builtin_names())]
+    UnknownGame(String),
+}
+
+fn builtin_names() -> String {
+    BUILTIN_PROFILES.map(|builtin| builtin.name).join(", ")
+}
+
+/// Everything the skipper knows about one game: how to find its window, what
+/// to look for on the screen and what to do about it, so detection can be
+/// tuned after a patch or for another game without recompiling.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameProfile {
+    /// Shown when the game is found.
+    #[serde(default = "GameProfile::unnamed")]
+    name: String,
+    /// Ways to recognise the game window, in order of preference.
+    #[serde(default)]
+    window: Vec<WindowMatcher>,
+    #[serde(default)]
+    fit: Fit,
+    #[serde(default)]
+    tolerance: ColorMatcher,
+    click_area: ClickArea,
+    probes: BTreeMap<String, ProbeDef>,
+    rules: Vec<RuleDef>,
+    /// Without it options are clicked somewhere in `click_area`.
+    options: Option<OptionColumnDef>,
+    /// Without it the auto-play strategy presses space like the default one.
+    auto_play: Option<AutoPlayDef>,
+    /// Without it cutscenes are never skipped.
+    cutscene: Option<CutsceneDef>,
+}
+
+/// Where option clicks land, in reference coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClickArea {
+    pub anchor: Anchor,
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeDef {
+    pub anchor: Anchor,
+    pub position: (i32, i32),
+    pub radius: i32,
+    pub color: Rgb,
+    /// Falls back to the profile-wide tolerance.
+    pub tolerance: Option<ColorMatcher>,
+}
+
+/// The column option bubbles are stacked in, in reference coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptionColumnDef {
+    pub anchor: Anchor,
+    pub x: i32,
+    pub top: i32,
+    pub bottom: i32,
+    pub color: Rgb,
+    /// Shorter runs of `color` are not bubbles.
+    pub min_height: i32,
+    /// Falls back to the profile-wide tolerance.
+    pub tolerance: Option<ColorMatcher>,
+}
+
+/// The game's own auto-play toggle, in reference coordinates.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutoPlayDef {
+    pub anchor: Anchor,
+    /// Where it is clicked.
+    pub position: (i32, i32),
+    /// The probe that matches while auto-play is running.
+    pub on: String,
+}
+
+/// The controls of a skippable cutscene.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CutsceneDef {
+    /// The skip control in the corner.
+    pub skip: CutsceneControlDef,
+    /// The button of the dialog asking whether to really skip.
+    pub confirm: CutsceneControlDef,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CutsceneControlDef {
+    /// Every listed probe has to match for the control to be on screen.
+    pub when: Vec<String>,
+    pub anchor: Anchor,
+    /// Where it is clicked, in reference coordinates.
+    pub click: (i32, i32),
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleDef {
+    /// Every listed probe has to match.
+    pub when: Vec<String>,
+    pub action: DialogAction,
+}
+
+impl GameProfile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut profile: Self = toml::from_str(text).wrap_err("Error parsing the game profile")?;
+        profile.validate()?;
+        // Profiles from before the window was configurable are for Genshin
+        if profile.window.is_empty() {
+            profile.window = Self::default().window;
+        }
+        Ok(profile)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Error reading {}", path.display()))?;
+        Self::parse(&text).wrap_err_with(|| format!("Invalid profile {}", path.display()))
+    }
+
+    fn unnamed() -> String {
+        "custom profile".to_string()
+    }
+
+    /// The built-in profile `--game` refers to by `name`.
+    pub fn builtin(name: &str) -> Result<Self> {
+        let text = Self::builtin_text(name)?;
+        Self::parse(text).wrap_err_with(|| format!("Invalid built-in profile {name}"))
+    }
+
+    pub fn builtin_text(name: &str) -> Result<&'static str, ProfileErr> {
+        BUILTIN_PROFILES
+            .iter()
+            .find(|builtin| builtin.name == name)
+            .map(|builtin| builtin.text)
+            .ok_or_else(|| ProfileErr::UnknownGame(name.to_string()))
+    }
+
+    /// Every built-in profile.
+    pub fn builtins() -> Vec<Self> {
+        Self::builtins_where(|_| true)
+    }
+
+    /// The built-in profiles to find whichever game is running with.
+    pub fn auto_detected() -> Vec<Self> {
+        Self::builtins_where(|builtin| builtin.auto_detect)
+    }
+
+    fn builtins_where(filter: impl Fn(&BuiltinProfile) -> bool) -> Vec<Self> {
+        BUILTIN_PROFILES
+            .iter()
+            .filter(|builtin| filter(builtin))
+            .map(|builtin| Self::builtin(builtin.name).expect("the built-in profiles are valid"))
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn window_matchers(&self) -> &[WindowMatcher] {
+        &self.window
+    }
+
+    pub fn fit(&self) -> Fit {
+        self.fit
+    }
+
+    pub fn click_area(&self) -> &ClickArea {
+        &self.click_area
+    }
+
+    pub fn probes(&self) -> impl Iterator<Item = (&str, &ProbeDef)> {
+        self.probes
+            .iter()
+            .map(|(name, probe)| (name.as_str(), probe))
+    }
+
+    pub fn rules(&self) -> &[RuleDef] {
+        &self.rules
+    }
+
+    pub fn probe_tolerance(&self, probe: &ProbeDef) -> ColorMatcher {
+        probe.tolerance.unwrap_or(self.tolerance)
+    }
+
+    /// The option column and the tolerance it uses.
+    pub fn options(&self) -> Option<(&OptionColumnDef, ColorMatcher)> {
+        self.options
+            .as_ref()
+            .map(|column| (column, column.tolerance.unwrap_or(self.tolerance)))
+    }
+
+    pub fn auto_play(&self) -> Option<&AutoPlayDef> {
+        self.auto_play.as_ref()
+    }
+
+    pub fn cutscene(&self) -> Option<&CutsceneDef> {
+        self.cutscene.as_ref()
+    }
+
+    fn validate(&self) -> Result<(), ProfileErr> {
+        if let Some(column) = &self.options {
+            if column.top >= column.bottom || column.min_height < 1 {
+                return Err(ProfileErr::InvalidOptionColumn);
+            }
+        }
+
+        for (name, probe) in &self.probes {
+            if probe.radius < 1 {
+                return Err(ProfileErr::InvalidRadius(name.clone()));
+            }
+        }
+
+        if let Some(auto_play) = &self.auto_play {
+            if !self.probes.contains_key(&auto_play.on) {
+                return Err(ProfileErr::UnknownAutoPlayProbe(auto_play.on.clone()));
+            }
+        }
+
+        if let Some(cutscene) = &self.cutscene {
+            for (control, def) in [("skip", &cutscene.skip), ("confirm", &cutscene.confirm)] {
+                if def.when.is_empty() {
+                    return Err(ProfileErr::EmptyCutsceneControl(control));
+                }
+                if let Some(probe) = def.when.iter().find(|p| !self.probes.contains_key(*p)) {
+                    return Err(ProfileErr::UnknownCutsceneProbe {
+                        control,
+                        probe: probe.clone(),
+                    });
+                }
+            }
+        }
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let rule_number = index + 1;
+            if rule.when.is_empty() {
+                return Err(ProfileErr::EmptyRule(rule_number));
+            }
+            if let Some(probe) = rule.when.iter().find(|p| !self.probes.contains_key(*p)) {
+                return Err(ProfileErr::UnknownProbe {
+                    rule: rule_number,
+                    probe: probe.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameProfile {
+    fn default() -> Self {
+        Self::parse(BUILTIN_PROFILES[0].text).expect("the built-in profiles are valid")
+    }
+}
+
+/// The first profile with a matching window, and that window. Profiles are
+/// tried in order, so the one given first wins if several games are open.
+/// `lookup` finds the executable of a window the title and class match.
+pub fn detect<'p, 'w>(
+    profiles: &'p [GameProfile],
+    windows: &'w [WindowInfo],
+    lookup: &impl Fn(usize) -> Option<String>,
+) -> Option<(&'p GameProfile, &'w WindowInfo)> {
+    profiles.iter().find_map(|profile| {
+        find_match(profile.window_matchers(), windows, lookup).map(|window| (profile, window))
+    })
+}
+
+/// The sections the README points to for adding to a profile.
+#[cfg(test)]
+pub const AUTO_PLAY_EXAMPLE: &str = include_str!("../profiles/examples/auto-play.toml");
+#[cfg(test)]
+pub const CUTSCENE_EXAMPLE: &str = include_str!("../profiles/examples/cutscene.toml");
+
+#[cfg(test)]
+impl GameProfile {
+    /// The Genshin profile with `extra` sections appended, for testing what
+    /// the built-in profiles do not ship with.
+    pub fn genshin_with(extra: &str) -> Self {
+        Self::parse(&format!("{}\n{extra}", BUILTIN_PROFILES[0].text)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENSHIN: &str = BUILTIN_PROFILES[0].text;
+
+    fn window(hwnd: usize, title: &str, exe: Option<&str>) -> WindowInfo {
+        WindowInfo::new(hwnd, title.to_string(), "UnityWndClass".to_string())
+            .with_exe(exe.map(str::to_string))
+    }
+
+    fn no_lookup(hwnd: usize) -> Option<String> {
+        panic!("Looked up the executable of window {hwnd}")
+    }
+
+    #[test]
+    fn builtin_profile_reproduces_the_original_constants() {
+        let profile = GameProfile::default();
+        assert_eq!(profile.fit(), Fit::Fill);
+        assert_eq!(
+            *profile.click_area(),
+            ClickArea {
+                anchor: Anchor::BOTTOM_CENTER,
+                min: (1300, 790),
+                max: (1700, 800),
+            }
+        );
+
+        let probes: BTreeMap<_, _> = profile.probes().collect();
+        assert_eq!(probes["playing_icon"].position, (84, 46));
+        assert_eq!(probes["playing_icon"].color, (236, 229, 216));
+        assert_eq!(probes["loading_screen"].position, (1200, 700));
+        assert_eq!(probes["dialogue_icon_lower"].position, (1301, 808));
+        assert_eq!(probes["dialogue_icon_higher"].position, (1301, 790));
+
+        let rules: Vec<_> = profile
+            .rules()
+            .iter()
+            .map(|rule| (rule.when.join(","), rule.action))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("playing_icon".to_string(), DialogAction::PressSpace),
+                ("loading_screen".to_string(), DialogAction::Wait),
+                ("dialogue_icon_lower".to_string(), DialogAction::ClickOption),
+                (
+                    "dialogue_icon_higher".to_string(),
+                    DialogAction::ClickOption
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn builtin_profile_finds_both_clients() {
+        let profile = GameProfile::default();
+        for (title, exe) in [
+            ("Genshin Impact", Some("GenshinImpact.exe")),
+            ("原神", Some("YuanShen.exe")),
+            // The process of an elevated game can not be looked at
+            ("原神", None),
+        ] {
+            assert!(
+                find_match(
+                    profile.window_matchers(),
+                    &[window(1, title, exe)],
+                    &no_lookup
+                )
+                .is_some(),
+                "{title} {exe:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn builtin_profiles_are_valid() {
+        let names: Vec<_> = GameProfile::builtins()
+            .iter()
+            .map(|profile| profile.name().to_string())
+            .collect();
+        assert_eq!(names, ["Genshin Impact", "Honkai: Star Rail"]);
+        assert_eq!(
+            GameProfile::builtin("genshin").unwrap(),
+            GameProfile::default()
+        );
+
+        let err = GameProfile::builtin("zzz").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown game `zzz`, the built-in ones are genshin, star-rail"
+        );
+    }
+
+    #[test]
+    fn detects_whichever_game_is_running() {
+        let profiles = GameProfile::builtins();
+        let browser = WindowInfo::new(
+            1,
+            "Honkai: Star Rail - Wiki".to_string(),
+            "Chrome_WidgetWin_1".to_string(),
+        );
+        let star_rail = window(2, "崩坏：星穹铁道", Some("StarRail.exe"));
+        let genshin = window(3, "Genshin Impact", Some("GenshinImpact.exe"));
+
+        let found = |windows: &[WindowInfo]| {
+            detect(&profiles, windows, &no_lookup)
+                .map(|(profile, window)| (profile.name().to_string(), window.hwnd))
+        };
+        assert_eq!(
+            found(&[browser.clone(), star_rail.clone()]),
+            Some(("Honkai: Star Rail".to_string(), 2))
+        );
+        // With both running the first profile wins
+        assert_eq!(
+            found(&[star_rail.clone(), genshin]),
+            Some(("Genshin Impact".to_string(), 3))
+        );
+        assert_eq!(found(&[browser]), None);
+
+        // Star Rail is only looked for when asked to, its positions are guessed
+        assert!(detect(&GameProfile::auto_detected(), &[star_rail], &no_lookup).is_none());
+    }
+
+    #[test]
+    fn tolerance_defaults_to_the_profile_value() {
+        let profile = GameProfile::parse(
+            r#"
+            tolerance = "exact"
+
+            [click_area]
+            anchor = "center"
+            min = [0, 0]
+            max = [10, 10]
+
+            [probes.strict]
+            anchor = "top-left"
+            position = [1, 1]
+            radius = 1
+            color = [0, 0, 0]
+
+            [probes.loose]
+            anchor = "top-left"
+            position = [1, 1]
+            radius = 1
+            color = [0, 0, 0]
+            tolerance = { per-channel = 4 }
+
+            [[rules]]
+            when = ["strict", "loose"]
+            action = "press-space"
+            "#,
+        )
+        .unwrap();
+
+        let probes: BTreeMap<_, _> = profile.probes().collect();
+        assert_eq!(
+            profile.probe_tolerance(probes["strict"]),
+            ColorMatcher::Exact
+        );
+        assert_eq!(
+            profile.probe_tolerance(probes["loose"]),
+            ColorMatcher::PerChannel(4)
+        );
+        // Without a [[window]] entry it looks for Genshin
+        assert_eq!(
+            profile.window_matchers(),
+            GameProfile::default().window_matchers()
+        );
+    }
+
+    #[test]
+    fn rules_must_refer_to_known_probes() {
+        let text = GENSHIN.replace("when = [\"loading_screen\"]", "when = [\"loading_screne\"]");
+        let err = GameProfile::parse(&text).unwrap_err();
+        assert!(err.to_string().contains("loading_screne"), "{err}");
+    }
+
+    #[test]
+    fn option_column_must_be_upright() {
+        assert!(GameProfile::default().options().is_some());
+        for (from, to) in [
+            ("top = 450", "top = 900"),
+            ("min_height = 8", "min_height = 0"),
+        ] {
+            let err = GameProfile::parse(&GENSHIN.replace(from, to)).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(ProfileErr::InvalidOptionColumn)),
+                "{err:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn auto_play_needs_a_known_probe() {
+        assert!(GameProfile::builtins()
+            .iter()
+            .all(|p| p.auto_play().is_none()));
+
+        let text = format!("{GENSHIN}\n[auto_play]\nanchor = \"top-left\"\nposition = [84, 46]\non = \"auto_play\"");
+        let err = GameProfile::parse(&text).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(ProfileErr::UnknownAutoPlayProbe(probe)) if probe == "auto_play"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn cutscene_controls_need_known_probes() {
+        assert!(GameProfile::builtins()
+            .iter()
+            .all(|p| p.cutscene().is_none()));
+
+        let cutscene = |skip: &str, confirm: &str| {
+            format!(
+                "{GENSHIN}
+                [cutscene.skip]
+                when = {skip}
+                anchor = \"top-right\"
+                click = [1800, 46]
+
+                [cutscene.confirm]
+                when = {confirm}
+                anchor = \"center\"
+                click = [1160, 760]"
+            )
+        };
+        assert!(
+            GameProfile::parse(&cutscene("[\"loading_screen\"]", "[\"playing_icon\"]")).is_ok()
+        );
+
+        let err = GameProfile::parse(&cutscene("[\"loading_screen\"]", "[]")).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref(),
+                Some(ProfileErr::EmptyCutsceneControl("confirm"))
+            ),
+            "{err:#}"
+        );
+
+        let err =
+            GameProfile::parse(&cutscene("[\"skip_buton\"]", "[\"playing_icon\"]")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The cutscene skip control refers to the unknown probe `skip_buton`"
+        );
+    }
+
+    #[test]
+    fn typos_in_keys_are_rejected() {
+        let text = GENSHIN.replace("radius = 8", "raduis = 8");
+        assert!(GameProfile::parse(&text).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    color::ColorMatcher,
+    coords::Rect,
+    pixel_source::{PixelSource, Rgb},
+};
+use color_eyre::Result;
+
+/// A small block of pixels around a probe point.
+///
+/// The region is sampled on a 3x3 grid (centre, edges and corners) and is considered
+/// to show a colour when the majority of the samples match it, so a single stray
+/// particle or a rounding error in the scaling cannot flip the verdict.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleRegion {
+    pub x: i32,
+    pub y: i32,
+    pub radius_x: i32,
+    pub radius_y: i32,
+}
+
+impl SampleRegion {
+    const GRID: [i32; 3] = [-1, 0, 1];
+
+    pub fn new(x: i32, y: i32, radius_x: i32, radius_y: i32) -> Self {
+        Self {
+            x,
+            y,
+            radius_x,
+            radius_y,
+        }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        Self::GRID.iter().flat_map(move |dy| {
+            Self::GRID
+                .iter()
+                .map(move |dx| (self.x + dx * self.radius_x, self.y + dy * self.radius_y))
+        })
+    }
+
+    /// Every pixel the samples can land on.
+    pub fn bounds(&self) -> Rect {
+        Rect {
+            left: self.x - self.radius_x,
+            top: self.y - self.radius_y,
+            right: self.x + self.radius_x + 1,
+            bottom: self.y + self.radius_y + 1,
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        Self::GRID.len() * Self::GRID.len()
+    }
+
+    /// `true` when more than half of the samples match `expected`.
+    pub fn matches(
+        &self,
+        source: &impl PixelSource,
+        matcher: &ColorMatcher,
+        expected: Rgb,
+    ) -> Result<bool> {
+        let mut votes = 0;
+        for (x, y) in self.points() {
+            if matcher.matches(source.get_pixel(x, y)?, expected) {
+                votes += 1;
+            }
+        }
+        Ok(votes * 2 > self.sample_count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_source::FrameBuffer;
+
+    const WHITE: Rgb = (255, 255, 255);
+    const BLACK: Rgb = (0, 0, 0);
+
+    #[test]
+    fn samples_a_three_by_three_grid() {
+        let region = SampleRegion::new(10, 20, 2, 1);
+        let points: Vec<_> = region.points().collect();
+        assert_eq!(points.len(), region.sample_count());
+        assert_eq!(points.first(), Some(&(8, 19)));
+        assert_eq!(points[4], (10, 20));
+        assert_eq!(points.last(), Some(&(12, 21)));
+    }
+
+    #[test]
+    fn single_stray_pixel_is_outvoted() {
+        let region = SampleRegion::new(10, 10, 2, 2);
+        let mut frame = FrameBuffer::new(20, 20, BLACK);
+        frame.set_pixel(10, 10, WHITE).unwrap();
+        assert!(!region.matches(&frame, &ColorMatcher::Exact, WHITE).unwrap());
+
+        frame.fill_rect(8, 8, 5, 5, WHITE);
+        frame.set_pixel(10, 10, BLACK).unwrap();
+        assert!(region.matches(&frame, &ColorMatcher::Exact, WHITE).unwrap());
+    }
+
+    #[test]
+    fn needs_a_strict_majority() {
+        let region = SampleRegion::new(10, 10, 2, 2);
+        let mut frame = FrameBuffer::new(20, 20, BLACK);
+        // The left column and the centre: 4 of 9 samples
+        frame.fill_rect(8, 8, 1, 5, WHITE);
+        frame.set_pixel(10, 10, WHITE).unwrap();
+        assert!(!region.matches(&frame, &ColorMatcher::Exact, WHITE).unwrap());
+
+        frame.set_pixel(10, 8, WHITE).unwrap();
+        assert!(region.matches(&frame, &ColorMatcher::Exact, WHITE).unwrap());
+    }
+}
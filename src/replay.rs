@@ -0,0 +1,234 @@
+use crate::{
+    genshin::{next_action, DialogAction, WindowProps},
+    pixel_source::FrameBuffer,
+    profile::GameProfile,
+};
+use color_eyre::{eyre::Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReplayErr {
+    #[error("{failed} of {total} file(s) could not be replayed")]
+    Failed { failed: usize, total: usize },
+}
+
+const IMAGE_EXTENSIONS: [&str; 2] = ["png", "bmp"];
+
+pub fn run(inputs: &[PathBuf], profile: &GameProfile) -> Result<()> {
+    let files = collect_images(inputs)?;
+    let mut failed = 0;
+
+    for file in &files {
+        match replay_file(file, profile) {
+            Ok(action) => println!("{}: {}", file.display(), describe(action)),
+            Err(e) => {
+                failed += 1;
+                println!("{}: error: {e:#}", file.display());
+            }
+        }
+    }
+
+    println!("{} file(s) replayed, {failed} failed", files.len());
+    if failed > 0 {
+        return Err(ReplayErr::Failed {
+            failed,
+            total: files.len(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub fn replay_file(path: &Path, profile: &GameProfile) -> Result<DialogAction> {
+    let frame = load_frame(path)?;
+    let props = WindowProps::new(&frame, profile)?;
+    next_action(&frame, &props)
+}
+
+pub fn load_frame(path: &Path) -> Result<FrameBuffer> {
+    let image = image::open(path)
+        .wrap_err_with(|| format!("Error loading {}", path.display()))?
+        .into_rgb8();
+    let (width, height) = image.dimensions();
+    let pixels = image.pixels().map(|p| (p[0], p[1], p[2])).collect();
+    FrameBuffer::from_pixels(width as i32, height as i32, pixels)
+}
+
+fn collect_images(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut entries = fs::read_dir(input)
+                .wrap_err_with(|| format!("Error reading {}", input.display()))?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.retain(|path| is_image(path));
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn describe(action: DialogAction) -> &'static str {
+    match action {
+        DialogAction::Wait => "no dialog",
+        DialogAction::PressSpace => "dialog, space",
+        DialogAction::ClickOption => "dialog, click",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genshin::{dialog_played, is_dialog_without_option};
+    use image::{Rgb, RgbImage};
+
+    const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+
+    fn parse_verdict(value: &str) -> bool {
+        match value {
+            "yes" => true,
+            "no" => false,
+            other => panic!("Unexpected verdict `{other}` in the corpus manifest"),
+        }
+    }
+
+    /// Frames whose verdicts disagree with `expected.txt` in `dir`, and how many were checked.
+    fn check_corpus(dir: &Path) -> (usize, Vec<String>) {
+        let manifest = fs::read_to_string(dir.join("expected.txt")).unwrap();
+        let profile = GameProfile::default();
+        let mut checked = 0;
+        let mut mismatches = Vec::new();
+
+        for line in manifest.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [file, dialog, without_option] = fields[..] else {
+                panic!("Malformed corpus manifest line: {line}");
+            };
+
+            let frame = load_frame(&dir.join(file)).unwrap();
+            let props = WindowProps::new(&frame, &profile).unwrap();
+            let actual = (
+                dialog_played(&frame, &props).unwrap(),
+                is_dialog_without_option(&frame, &props).unwrap(),
+            );
+            let expected = (parse_verdict(dialog), parse_verdict(without_option));
+            if actual != expected {
+                mismatches.push(format!("{file}: expected {expected:?}, got {actual:?}"));
+            }
+            checked += 1;
+        }
+        (checked, mismatches)
+    }
+
+    #[test]
+    fn corpus_matches_expected_verdicts() {
+        let (checked, mismatches) = check_corpus(Path::new(CORPUS_DIR));
+        assert!(checked > 0, "The corpus manifest lists no frames");
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+
+    /// Screenshots of the game itself, the synthetic frames above only check
+    /// the probes against a mock of the UI.
+    #[test]
+    #[ignore = "tests/corpus/captures has no game screenshots yet"]
+    fn captures_match_expected_verdicts() {
+        let (checked, mismatches) = check_corpus(&Path::new(CORPUS_DIR).join("captures"));
+        assert!(checked > 0, "The captures manifest lists no frames");
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+
+    #[test]
+    fn every_corpus_frame_is_annotated() {
+        let corpus = Path::new(CORPUS_DIR);
+        for dir in [corpus.to_path_buf(), corpus.join("captures")] {
+            let manifest = fs::read_to_string(dir.join("expected.txt")).unwrap();
+            for frame in collect_images(&[dir]).unwrap() {
+                let name = frame.file_name().unwrap().to_str().unwrap();
+                assert!(
+                    manifest
+                        .lines()
+                        .any(|line| line.split_whitespace().next() == Some(name)),
+                    "{name} has no expected verdict in expected.txt"
+                );
+            }
+        }
+    }
+
+    /// A folder of its own for one test, removed again even if the test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let name = format!("genshin-auto-skipper-{}-{test}", std::process::id());
+            let dir = std::env::temp_dir().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn replays_saved_screenshot() {
+        let temp = TempDir::new("replays_saved_screenshot");
+        let dir = temp.0.clone();
+
+        let mut image = RgbImage::from_pixel(1920, 1080, Rgb([40, 60, 90]));
+        for (x, y) in (72..96).flat_map(|x| (34..58).map(move |y| (x, y))) {
+            image.put_pixel(x, y, Rgb([236, 229, 216]));
+        }
+        let dialog = dir.join("dialog.png");
+        image.save(&dialog).unwrap();
+
+        let world = dir.join("world.bmp");
+        RgbImage::from_pixel(1920, 1080, Rgb([40, 60, 90]))
+            .save(&world)
+            .unwrap();
+
+        let profile = GameProfile::default();
+        assert_eq!(
+            replay_file(&dialog, &profile).unwrap(),
+            DialogAction::PressSpace
+        );
+        assert_eq!(replay_file(&world, &profile).unwrap(), DialogAction::Wait);
+        assert_eq!(collect_images(&[dir]).unwrap(), vec![dialog, world]);
+    }
+
+    #[test]
+    fn fails_if_any_file_fails() {
+        let temp = TempDir::new("fails_if_any_file_fails");
+        let inputs = [temp.0.clone()];
+        let world = temp.0.join("world.png");
+        RgbImage::from_pixel(1920, 1080, Rgb([40, 60, 90]))
+            .save(&world)
+            .unwrap();
+        let profile = GameProfile::default();
+        run(&inputs, &profile).unwrap();
+
+        fs::write(temp.0.join("truncated.png"), b"\x89PNG").unwrap();
+        let err = run(&inputs, &profile).unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 file(s) could not be replayed");
+    }
+}
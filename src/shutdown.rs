@@ -0,0 +1,115 @@
+use color_eyre::{eyre::eyre, Report, Result};
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ShutdownErr {
+    #[error("The {0} did not stop within {1:?}")]
+    Timeout(&'static str, Duration),
+    #[error("The {0} panicked: {1}")]
+    Panicked(&'static str, String),
+}
+
+/// `JoinHandle::join` without the risk of hanging on exit. A thread that does
+/// not finish in time is left running and reported.
+pub fn join_timeout(
+    name: &'static str,
+    handle: JoinHandle<Result<()>>,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return Err(ShutdownErr::Timeout(name, timeout).into());
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    match handle.join() {
+        Ok(result) => result.map_err(|e| e.wrap_err(format!("Error inside the {name}"))),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(ShutdownErr::Panicked(name, message).into())
+        }
+    }
+}
+
+/// Puts the outcome of the main loop and of every worker into one result.
+/// The first error is returned, the others are printed as they would get lost otherwise.
+pub fn combine(results: impl IntoIterator<Item = Result<()>>) -> Result<()> {
+    let mut errors = results.into_iter().filter_map(Result::err);
+    let Some(first) = errors.next() else {
+        return Ok(());
+    };
+    for other in errors {
+        eprintln!("Also failed: {other:?}");
+    }
+    Err(first)
+}
+
+/// Error for a worker that ended on its own while the app was still running.
+pub fn stopped_unexpectedly(name: &'static str) -> Report {
+    eyre!("The {name} stopped unexpectedly")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn returns_the_thread_result() {
+        let ok = thread::spawn(|| Ok(()));
+        assert!(join_timeout("worker", ok, TIMEOUT).is_ok());
+
+        let failed = thread::spawn(|| Err(eyre!("capture failed")));
+        let err = join_timeout("worker", failed, TIMEOUT).unwrap_err();
+        assert!(format!("{err:#}").contains("capture failed"), "{err:#}");
+        assert!(
+            format!("{err:#}").contains("Error inside the worker"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn gives_up_on_a_stuck_thread() {
+        let (unblock, blocked) = channel::<()>();
+        let stuck = thread::spawn(move || {
+            let _ = blocked.recv();
+            Ok(())
+        });
+
+        let started = Instant::now();
+        let err = join_timeout("listener", stuck, TIMEOUT).unwrap_err();
+        assert!(started.elapsed() >= TIMEOUT);
+        assert!(matches!(
+            err.downcast_ref::<ShutdownErr>(),
+            Some(ShutdownErr::Timeout("listener", _))
+        ));
+        drop(unblock);
+    }
+
+    #[test]
+    fn reports_panics() {
+        let panicking = thread::spawn(|| -> Result<()> { panic!("no window") });
+        let err = join_timeout("sender", panicking, TIMEOUT).unwrap_err();
+        assert_eq!(err.to_string(), "The sender panicked: no window");
+    }
+
+    #[test]
+    fn returns_the_first_error() {
+        assert!(combine([Ok(()), Ok(())]).is_ok());
+
+        let err = combine([Ok(()), Err(eyre!("first")), Err(eyre!("second"))]).unwrap_err();
+        assert_eq!(err.to_string(), "first");
+    }
+}
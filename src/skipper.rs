@@ -0,0 +1,259 @@
+use std::fmt;
+
+/// Where the skipper is at, everything the main loop and the sender agree on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SkipperState {
+    /// Not started yet.
+    #[default]
+    Idle,
+    /// Started, but the game is not running.
+    ArmedNoWindow,
+    /// Started, but another window has the focus.
+    ArmedWindowInactive,
+    Skipping,
+    /// Stopped by the user.
+    Paused,
+    ShuttingDown,
+}
+
+impl SkipperState {
+    pub const ALL: [SkipperState; 6] = [
+        SkipperState::Idle,
+        SkipperState::ArmedNoWindow,
+        SkipperState::ArmedWindowInactive,
+        SkipperState::Skipping,
+        SkipperState::Paused,
+        SkipperState::ShuttingDown,
+    ];
+
+    pub fn is_armed(self) -> bool {
+        matches!(
+            self,
+            SkipperState::ArmedNoWindow
+                | SkipperState::ArmedWindowInactive
+                | SkipperState::Skipping
+        )
+    }
+
+    fn armed(window: WindowStatus) -> Self {
+        match window {
+            WindowStatus::Missing => SkipperState::ArmedNoWindow,
+            WindowStatus::Inactive => SkipperState::ArmedWindowInactive,
+            WindowStatus::Active => SkipperState::Skipping,
+        }
+    }
+}
+
+impl fmt::Display for SkipperState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipperState::Idle => "Idle",
+            SkipperState::ArmedNoWindow => "Waiting for the game to start",
+            SkipperState::ArmedWindowInactive => "Waiting for the game window to get focus",
+            SkipperState::Skipping => "Skipping",
+            SkipperState::Paused => "Paused",
+            SkipperState::ShuttingDown => "Shutting down",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowStatus {
+    #[default]
+    Missing,
+    Inactive,
+    Active,
+}
+
+/// What an `EventType` means once the hotkeys and the game window have been looked at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Start,
+    Stop,
+    Window(WindowStatus),
+    Shutdown,
+}
+
+impl Input {
+    pub const ALL: [Input; 6] = [
+        Input::Start,
+        Input::Stop,
+        Input::Window(WindowStatus::Missing),
+        Input::Window(WindowStatus::Inactive),
+        Input::Window(WindowStatus::Active),
+        Input::Shutdown,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub from: SkipperState,
+    pub to: SkipperState,
+    pub input: Input,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Skipper {
+    state: SkipperState,
+    /// Last known state of the game window, also kept while not armed so
+    /// starting goes straight to the right state.
+    window: WindowStatus,
+}
+
+impl Skipper {
+    pub fn state(&self) -> SkipperState {
+        self.state
+    }
+
+    /// Applies the input, returns the transition if the state changed.
+    pub fn on_input(&mut self, input: Input) -> Option<Transition> {
+        if let Input::Window(window) = input {
+            self.window = window;
+        }
+
+        let from = self.state;
+        let to = match (from, input) {
+            (SkipperState::ShuttingDown, _) => SkipperState::ShuttingDown,
+            (_, Input::Shutdown) => SkipperState::ShuttingDown,
+            (SkipperState::Idle | SkipperState::Paused, Input::Start) => {
+                SkipperState::armed(self.window)
+            }
+            (armed, Input::Stop) if armed.is_armed() => SkipperState::Paused,
+            (armed, Input::Window(window)) if armed.is_armed() => SkipperState::armed(window),
+            (unchanged, _) => unchanged,
+        };
+
+        self.state = to;
+        (from != to).then_some(Transition { from, to, input })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SkipperState::*;
+    use WindowStatus::*;
+
+    #[test]
+    fn transition_table() {
+        // (state, input, next state). The remembered window is active, so
+        // starting goes straight to skipping.
+        #[rustfmt::skip]
+        let table = [
+            (Idle, Input::Start, Skipping),
+            (Idle, Input::Stop, Idle),
+            (Idle, Input::Window(Missing), Idle),
+            (Idle, Input::Window(Inactive), Idle),
+            (Idle, Input::Window(Active), Idle),
+            (Idle, Input::Shutdown, ShuttingDown),
+
+            (ArmedNoWindow, Input::Start, ArmedNoWindow),
+            (ArmedNoWindow, Input::Stop, Paused),
+            (ArmedNoWindow, Input::Window(Missing), ArmedNoWindow),
+            (ArmedNoWindow, Input::Window(Inactive), ArmedWindowInactive),
+            (ArmedNoWindow, Input::Window(Active), Skipping),
+            (ArmedNoWindow, Input::Shutdown, ShuttingDown),
+
+            (ArmedWindowInactive, Input::Start, ArmedWindowInactive),
+            (ArmedWindowInactive, Input::Stop, Paused),
+            (ArmedWindowInactive, Input::Window(Missing), ArmedNoWindow),
+            (ArmedWindowInactive, Input::Window(Inactive), ArmedWindowInactive),
+            (ArmedWindowInactive, Input::Window(Active), Skipping),
+            (ArmedWindowInactive, Input::Shutdown, ShuttingDown),
+
+            (Skipping, Input::Start, Skipping),
+            (Skipping, Input::Stop, Paused),
+            (Skipping, Input::Window(Missing), ArmedNoWindow),
+            (Skipping, Input::Window(Inactive), ArmedWindowInactive),
+            (Skipping, Input::Window(Active), Skipping),
+            (Skipping, Input::Shutdown, ShuttingDown),
+
+            (Paused, Input::Start, Skipping),
+            (Paused, Input::Stop, Paused),
+            (Paused, Input::Window(Missing), Paused),
+            (Paused, Input::Window(Inactive), Paused),
+            (Paused, Input::Window(Active), Paused),
+            (Paused, Input::Shutdown, ShuttingDown),
+
+            (ShuttingDown, Input::Start, ShuttingDown),
+            (ShuttingDown, Input::Stop, ShuttingDown),
+            (ShuttingDown, Input::Window(Missing), ShuttingDown),
+            (ShuttingDown, Input::Window(Inactive), ShuttingDown),
+            (ShuttingDown, Input::Window(Active), ShuttingDown),
+            (ShuttingDown, Input::Shutdown, ShuttingDown),
+        ];
+
+        for state in SkipperState::ALL {
+            for input in Input::ALL {
+                let rows = table
+                    .iter()
+                    .filter(|(s, i, _)| *s == state && *i == input)
+                    .count();
+                assert_eq!(rows, 1, "{state:?} on {input:?} has {rows} rows");
+            }
+        }
+
+        for (state, input, expected) in table {
+            // Window inputs leave the remembered window on the new value, any
+            // other input keeps it active for the start rows
+            let mut skipper = Skipper {
+                state,
+                window: Active,
+            };
+            let transition = skipper.on_input(input);
+            assert_eq!(skipper.state(), expected, "{state:?} on {input:?}");
+            assert_eq!(
+                transition,
+                (state != expected).then_some(Transition {
+                    from: state,
+                    to: expected,
+                    input
+                }),
+                "{state:?} on {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn start_uses_the_last_known_window() {
+        let mut skipper = Skipper::default();
+        skipper.on_input(Input::Window(Inactive));
+        skipper.on_input(Input::Start);
+        assert_eq!(skipper.state(), ArmedWindowInactive);
+
+        skipper.on_input(Input::Stop);
+        skipper.on_input(Input::Window(Missing));
+        assert_eq!(skipper.state(), Paused);
+        skipper.on_input(Input::Start);
+        assert_eq!(skipper.state(), ArmedNoWindow);
+    }
+
+    #[test]
+    fn a_session_emits_each_change_once() {
+        let mut skipper = Skipper::default();
+        let changes: Vec<_> = [
+            Input::Start,
+            Input::Window(Inactive),
+            Input::Window(Active),
+            Input::Window(Active),
+            Input::Stop,
+            Input::Shutdown,
+            Input::Start,
+        ]
+        .into_iter()
+        .filter_map(|input| skipper.on_input(input))
+        .map(|transition| transition.to)
+        .collect();
+
+        assert_eq!(
+            changes,
+            [
+                ArmedNoWindow,
+                ArmedWindowInactive,
+                Skipping,
+                Paused,
+                ShuttingDown
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    cutscene::CutsceneSkip,
+    genshin::{auto_play_on, cutscene_control, react, Reaction, WindowProps},
+    options::{ChoiceState, ChoiceWait, OptionChoice},
+    pixel_source::PixelSource,
+};
+use color_eyre::Result;
+use serde::Deserialize;
+
+/// How the sender gets through lines without options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipStrategy {
+    /// Press space on every line.
+    #[default]
+    PressSpace,
+    /// Turn on the game's own auto-play and leave the lines to it, only
+    /// stepping in for options.
+    AutoPlay,
+}
+
+/// A `SkipStrategy` with what it remembers between frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Strategy {
+    kind: SkipStrategy,
+    /// The toggle was clicked in this conversation.
+    toggled: bool,
+    /// `None` if cutscenes are left to play.
+    cutscenes: Option<CutsceneSkip>,
+    choices: ChoiceWait,
+}
+
+impl Strategy {
+    pub fn new(kind: SkipStrategy, skip_cutscenes: bool) -> Self {
+        Self {
+            kind,
+            toggled: false,
+            cutscenes: skip_cutscenes.then(CutsceneSkip::default),
+            choices: ChoiceWait::default(),
+        }
+    }
+
+    /// Whether the player is being waited on to pick an option.
+    pub fn choice_state(&self) -> ChoiceState {
+        self.choices.state()
+    }
+
+    /// What to do about a frame, picking among the options with `choice`.
+    pub fn react(
+        &mut self,
+        source: &impl PixelSource,
+        props: &WindowProps,
+        choice: OptionChoice,
+    ) -> Result<Reaction> {
+        let reaction = react(source, props, choice)?;
+        // The bubbles are only looked for while the player is choosing
+        let options_up = self.choices.state() == ChoiceState::AwaitingChoice
+            && !props.find_options(source)?.is_empty();
+        let reaction = self.choices.hold(reaction, options_up);
+        let reaction = match self.kind {
+            SkipStrategy::PressSpace => reaction,
+            SkipStrategy::AutoPlay => {
+                self.leave_to_auto_play(reaction, auto_play_on(source, props)?)
+            }
+        };
+        // Cutscenes only get a look when there is no dialogue to deal with
+        Ok(match &mut self.cutscenes {
+            Some(cutscenes) if reaction == Reaction::Wait => cutscenes
+                .on_frame(cutscene_control(source, props)?)
+                .map_or(Reaction::Wait, Reaction::ClickCutscene),
+            Some(cutscenes) => {
+                cutscenes.reset();
+                reaction
+            }
+            None => reaction,
+        })
+    }
+
+    /// Turns a space press into a click on the auto-play toggle, once per
+    /// conversation. If auto-play is still off after that, whether the click
+    /// did not take or the player turned it off again, space is pressed as usual.
+    fn leave_to_auto_play(&mut self, reaction: Reaction, auto_play_on: Option<bool>) -> Reaction {
+        let Some(on) = auto_play_on else {
+            return reaction;
+        };
+        match reaction {
+            Reaction::ClickOption(_)
+            | Reaction::AwaitChoice
+            | Reaction::ToggleAutoPlay
+            | Reaction::ClickCutscene(_) => reaction,
+            _ if on => Reaction::Wait,
+            Reaction::PressSpace if !self.toggled => {
+                self.toggled = true;
+                Reaction::ToggleAutoPlay
+            }
+            Reaction::PressSpace => Reaction::PressSpace,
+            // Neither dialogue nor auto-play, the conversation is over
+            Reaction::Wait => {
+                self.toggled = false;
+                Reaction::Wait
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pixel_source::{FrameBuffer, Rgb},
+        profile::{GameProfile, AUTO_PLAY_EXAMPLE},
+    };
+
+    const ICON: Rgb = (236, 229, 216);
+    const WHITE: Rgb = (255, 255, 255);
+
+    struct Screens {
+        props: WindowProps,
+        nothing: FrameBuffer,
+        dialogue: FrameBuffer,
+        auto_playing: FrameBuffer,
+        options: FrameBuffer,
+    }
+
+    fn screens() -> Screens {
+        let (nothing, props) = FrameBuffer::screen(&GameProfile::genshin_with(AUTO_PLAY_EXAMPLE));
+        let mut dialogue = nothing.clone();
+        dialogue.paint(&props, "playing_icon", ICON);
+        let mut auto_playing = dialogue.clone();
+        auto_playing.paint(&props, "auto_play_on", ICON);
+        // The top left icon is hidden while options are up
+        let mut options = nothing.clone();
+        options.fill_rect(1290, 800, 12, 17, WHITE);
+        Screens {
+            props,
+            nothing,
+            dialogue,
+            auto_playing,
+            options,
+        }
+    }
+
+    #[test]
+    fn turns_auto_play_on_once_per_conversation() {
+        let s = screens();
+        let mut strategy = Strategy::new(SkipStrategy::AutoPlay, false);
+
+        // (frame, what the sender does)
+        #[rustfmt::skip]
+        let script = [
+            (&s.nothing, Reaction::Wait),
+            (&s.dialogue, Reaction::ToggleAutoPlay),
+            // The game has not redrawn the toggle yet
+            (&s.dialogue, Reaction::PressSpace),
+            (&s.auto_playing, Reaction::Wait),
+            (&s.auto_playing, Reaction::Wait),
+            (&s.options, Reaction::ClickOption(Some(808))),
+            (&s.auto_playing, Reaction::Wait),
+            // Turned off by the player, not fought over
+            (&s.dialogue, Reaction::PressSpace),
+            (&s.nothing, Reaction::Wait),
+            // The next conversation
+            (&s.dialogue, Reaction::ToggleAutoPlay),
+        ];
+        for (i, (frame, expected)) in script.into_iter().enumerate() {
+            let got = strategy.react(frame, &s.props, OptionChoice::Last).unwrap();
+            assert_eq!(got, expected, "frame {i}");
+        }
+    }
+
+    #[test]
+    fn pressing_space_ignores_auto_play() {
+        let s = screens();
+        let mut strategy = Strategy::new(SkipStrategy::PressSpace, false);
+        for frame in [&s.dialogue, &s.auto_playing, &s.dialogue] {
+            assert_eq!(
+                strategy.react(frame, &s.props, OptionChoice::Last).unwrap(),
+                Reaction::PressSpace
+            );
+        }
+    }
+
+    #[test]
+    fn options_still_pause_with_auto_play() {
+        let s = screens();
+        let mut strategy = Strategy::new(SkipStrategy::AutoPlay, false);
+        assert_eq!(
+            strategy
+                .react(&s.options, &s.props, OptionChoice::Pause)
+                .unwrap(),
+            Reaction::AwaitChoice
+        );
+    }
+
+    #[test]
+    fn presses_space_without_a_toggle_in_the_profile() {
+        let props = WindowProps::from_size(1920, 1080, &GameProfile::default());
+        let dialogue = screens().dialogue;
+        let mut strategy = Strategy::new(SkipStrategy::AutoPlay, false);
+        for _ in 0..3 {
+            assert_eq!(
+                strategy
+                    .react(&dialogue, &props, OptionChoice::Last)
+                    .unwrap(),
+                Reaction::PressSpace
+            );
+        }
+    }
+
+    #[test]
+    fn reads_the_strategy_from_toml() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            strategy: SkipStrategy,
+        }
+        let parse = |text: &str| toml::from_str::<Wrapper>(text).map(|w| w.strategy);
+        assert_eq!(
+            parse(r#"strategy = "auto-play""#).unwrap(),
+            SkipStrategy::AutoPlay
+        );
+        assert_eq!(
+            parse(r#"strategy = "press-space""#).unwrap(),
+            SkipStrategy::PressSpace
+        );
+        assert!(parse(r#"strategy = "spam""#).is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use crate::pixel_source::PixelSourceErr;
+use color_eyre::{Report, Result};
+use std::{
+    collections::VecDeque,
+    io,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Likely to go away on its own, such as a capture failing while the game
+    /// changes resolution or the user alt-tabs.
+    Transient,
+    /// Restarting will not help.
+    Fatal,
+}
+
+/// `ERROR_ACCESS_DENIED`, the secure desktop of a UAC prompt or the lock screen is up.
+const ERROR_ACCESS_DENIED: i32 = 5;
+/// `ERROR_INVALID_WINDOW_HANDLE`, the game window closed between two calls.
+const ERROR_INVALID_WINDOW_HANDLE: i32 = 1400;
+
+/// Frames that cannot be read or do not fit the probes, and Windows calls
+/// failing because the game window is out of reach for now, are transient.
+/// Any other failure is a bug or a broken setup.
+pub fn classify(error: &Report) -> ErrorKind {
+    let transient = error.chain().any(|cause| {
+        if cause.is::<PixelSourceErr>() {
+            return true;
+        }
+        cause
+            .downcast_ref::<io::Error>()
+            .and_then(io::Error::raw_os_error)
+            .is_some_and(|code| [ERROR_ACCESS_DENIED, ERROR_INVALID_WINDOW_HANDLE].contains(&code))
+    });
+    if transient {
+        ErrorKind::Transient
+    } else {
+        ErrorKind::Fatal
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    RestartAfter(Duration),
+    GiveUp,
+}
+
+/// When to restart a worker and when to stop trying.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    /// Giving up once this many transient failures happen within `window`.
+    pub max_failures: usize,
+    pub window: Duration,
+    /// Wait before the first restart, doubled for every recent failure.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    recent: VecDeque<Instant>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        // The longest wait stays below the shutdown timeout, a worker backing
+        // off still stops in time
+        Self::new(
+            5,
+            Duration::from_secs(60),
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+        )
+    }
+}
+
+impl RestartPolicy {
+    pub fn new(
+        max_failures: usize,
+        window: Duration,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        Self {
+            max_failures,
+            window,
+            initial_backoff,
+            max_backoff,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn on_failure(&mut self, kind: ErrorKind, now: Instant) -> Decision {
+        if kind == ErrorKind::Fatal {
+            return Decision::GiveUp;
+        }
+
+        while let Some(oldest) = self.recent.front() {
+            if now.duration_since(*oldest) < self.window {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now);
+
+        let failures = self.recent.len();
+        if failures >= self.max_failures {
+            return Decision::GiveUp;
+        }
+        let doublings = u32::try_from(failures - 1).unwrap_or(u32::MAX).min(16);
+        let backoff = self.initial_backoff.saturating_mul(1 << doublings);
+        Decision::RestartAfter(backoff.min(self.max_backoff))
+    }
+}
+
+/// Runs `attempt` until it succeeds, restarting it on transient errors as the
+/// policy allows. `wait` sleeps between attempts and returns `false` if the
+/// worker is asked to stop in the meantime.
+pub fn supervise(
+    name: &str,
+    mut policy: RestartPolicy,
+    mut wait: impl FnMut(Duration) -> bool,
+    mut attempt: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    loop {
+        let error = match attempt() {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        let kind = classify(&error);
+        match policy.on_failure(kind, Instant::now()) {
+            Decision::RestartAfter(backoff) => {
+                eprintln!("The {name} failed, restarting in {backoff:?}: {error:#}");
+                if !wait(backoff) {
+                    return Ok(());
+                }
+            }
+            Decision::GiveUp if kind == ErrorKind::Fatal => return Err(error),
+            Decision::GiveUp => {
+                return Err(error.wrap_err(format!(
+                    "The {name} failed {} times in {:?}, giving up",
+                    policy.max_failures, policy.window
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        genshin::{next_action, WindowProps},
+        pixel_source::{FrameBuffer, PixelSource, Rgb},
+        profile::GameProfile,
+        worker::{self, TargetSlot},
+    };
+    use color_eyre::eyre::eyre;
+    use std::cell::Cell;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy::new(4, 1000 * MS, 100 * MS, 500 * MS)
+    }
+
+    #[test]
+    fn classifies_by_cause() {
+        let capture: Report = PixelSourceErr::Unreadable(io::Error::from_raw_os_error(0)).into();
+        assert_eq!(classify(&capture), ErrorKind::Transient);
+        let closed: Report = io::Error::from_raw_os_error(ERROR_INVALID_WINDOW_HANDLE).into();
+        assert_eq!(classify(&closed), ErrorKind::Transient);
+
+        // A hook or message queue that fails is not going to get better
+        let hook: Report = io::Error::from_raw_os_error(1428).into();
+        assert_eq!(classify(&hook), ErrorKind::Fatal);
+        let other: Report = io::Error::from(io::ErrorKind::Other).into();
+        assert_eq!(classify(&other), ErrorKind::Fatal);
+
+        let resized: Report = PixelSourceErr::OutOfBounds {
+            x: 1301,
+            y: 790,
+            width: 800,
+            height: 600,
+        }
+        .into();
+        assert_eq!(
+            classify(&resized.wrap_err("Error inside the message sender")),
+            ErrorKind::Transient
+        );
+
+        assert_eq!(classify(&eyre!("no profile")), ErrorKind::Fatal);
+    }
+
+    #[test]
+    fn backs_off_then_gives_up() {
+        let mut policy = policy();
+        let start = Instant::now();
+        let decisions: Vec<_> = [0, 10, 20, 30]
+            .into_iter()
+            .map(|ms| policy.on_failure(ErrorKind::Transient, start + ms * MS))
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                Decision::RestartAfter(100 * MS),
+                Decision::RestartAfter(200 * MS),
+                Decision::RestartAfter(400 * MS),
+                Decision::GiveUp,
+            ]
+        );
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut policy = RestartPolicy::new(10, 1000 * MS, 100 * MS, 500 * MS);
+        let start = Instant::now();
+        let last = (0..9)
+            .map(|i| policy.on_failure(ErrorKind::Transient, start + i * MS))
+            .last();
+        assert_eq!(last, Some(Decision::RestartAfter(500 * MS)));
+    }
+
+    #[test]
+    fn old_failures_are_forgotten() {
+        let mut policy = policy();
+        let start = Instant::now();
+        for i in 0..10 {
+            // One failure every 600 ms never piles up within the 1 s window
+            let decision = policy.on_failure(ErrorKind::Transient, start + i * 600 * MS);
+            assert!(matches!(decision, Decision::RestartAfter(_)), "{i}");
+        }
+    }
+
+    #[test]
+    fn fatal_errors_are_not_retried() {
+        let mut policy = policy();
+        assert_eq!(
+            policy.on_failure(ErrorKind::Fatal, Instant::now()),
+            Decision::GiveUp
+        );
+    }
+
+    /// A game window that is mid resolution change for the first few captures.
+    struct FlakySource {
+        frame: FrameBuffer,
+        failures_left: Cell<usize>,
+        reads: Cell<usize>,
+    }
+
+    impl FlakySource {
+        fn new(failures: usize) -> Self {
+            Self {
+                frame: FrameBuffer::new(1920, 1080, (40, 60, 80)),
+                failures_left: Cell::new(failures),
+                reads: Cell::new(0),
+            }
+        }
+    }
+
+    impl PixelSource for FlakySource {
+        fn get_pixel(&self, x: i32, y: i32) -> Result<Rgb> {
+            self.reads.set(self.reads.get() + 1);
+            if self.failures_left.get() > 0 {
+                self.failures_left.set(self.failures_left.get() - 1);
+                return Err(PixelSourceErr::SizeMismatch {
+                    width: 1280,
+                    height: 720,
+                    expected: 1920 * 1080,
+                    actual: 1280 * 720,
+                }
+                .into());
+            }
+            self.frame.get_pixel(x, y)
+        }
+
+        fn size(&self) -> Result<(i32, i32)> {
+            self.frame.size()
+        }
+    }
+
+    /// The sender loop on a fake game: stops after `frames` detections in a row.
+    fn run_sender(source: &FlakySource, waits: &mut Vec<Duration>, frames: usize) -> Result<()> {
+        let props = WindowProps::new(&source.frame, &GameProfile::default())?;
+        let slot = TargetSlot::new();
+        slot.set(Some(()));
+        let mut detected = 0;
+
+        supervise(
+            "message sender",
+            policy(),
+            |backoff| {
+                waits.push(backoff);
+                true
+            },
+            || {
+                worker::run(&slot, Duration::ZERO, |_| {
+                    let action = next_action(source, &props)?;
+                    detected += 1;
+                    if detected == frames {
+                        slot.close();
+                    }
+                    Ok(action)
+                })
+            },
+        )
+    }
+
+    #[test]
+    fn restarts_the_sender_through_a_resolution_change() {
+        let source = FlakySource::new(2);
+        let mut waits = Vec::new();
+        run_sender(&source, &mut waits, 3).unwrap();
+        assert_eq!(waits, [100 * MS, 200 * MS]);
+        assert_eq!(source.failures_left.get(), 0);
+    }
+
+    #[test]
+    fn gives_up_on_a_source_that_keeps_failing() {
+        let source = FlakySource::new(usize::MAX);
+        let mut waits = Vec::new();
+        let err = run_sender(&source, &mut waits, 3).unwrap_err();
+        assert_eq!(waits.len(), 3);
+        assert!(err.to_string().contains("giving up"), "{err}");
+        assert_eq!(source.reads.get(), 4);
+    }
+
+    #[test]
+    fn stops_waiting_when_asked_to() {
+        let mut attempts = 0;
+        let result = supervise(
+            "event listener",
+            policy(),
+            |_| false,
+            || {
+                attempts += 1;
+                Err(io::Error::from_raw_os_error(ERROR_ACCESS_DENIED).into())
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn fatal_errors_end_the_worker_right_away() {
+        let mut attempts = 0;
+        let result = supervise(
+            "message sender",
+            policy(),
+            |_| true,
+            || {
+                attempts += 1;
+                Err(eyre!("bad profile"))
+            },
+        );
+        assert_eq!(result.unwrap_err().to_string(), "bad profile");
+        assert_eq!(attempts, 1);
+    }
+}
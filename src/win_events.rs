@@ -0,0 +1,280 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// `OBJID_WINDOW` and `CHILDID_SELF`: the event is about the window itself,
+/// not a caret, scroll bar or other part of it.
+const OBJID_WINDOW: i32 = 0;
+const CHILDID_SELF: i32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WinEventKind {
+    Foreground,
+    Destroy,
+    /// Moved or resized.
+    LocationChange,
+}
+
+impl WinEventKind {
+    const COUNT: usize = 3;
+
+    fn index(self) -> usize {
+        match self {
+            WinEventKind::Foreground => 0,
+            WinEventKind::Destroy => 1,
+            WinEventKind::LocationChange => 2,
+        }
+    }
+}
+
+/// What a WinEvent hook was told, with the window handle kept as a number so
+/// it can cross threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WinEvent {
+    pub kind: WinEventKind,
+    pub hwnd: usize,
+    pub id_object: i32,
+    pub id_child: i32,
+    /// Process owning the window, only looked up when the game window is
+    /// destroyed. `None` if it was not, or the window was already gone.
+    pub pid: Option<u32>,
+}
+
+impl WinEvent {
+    pub fn is_top_level_window(&self) -> bool {
+        self.id_object == OBJID_WINDOW && self.id_child == CHILDID_SELF && self.hwnd != 0
+    }
+}
+
+/// Decides in the hook which WinEvents are worth waking the main loop for.
+///
+/// Destroy and location events fire for every tooltip, caret and cursor move
+/// on the system, only those about the game's window are kept.
+/// Each kind is also sent at most once until the main loop has picked it up,
+/// since it looks at the windows again anyway.
+#[derive(Debug)]
+pub struct WinEventFilter {
+    /// Handle and process of the game window, zero while there is none.
+    game_window: AtomicUsize,
+    game_pid: AtomicU32,
+    pending: [AtomicBool; WinEventKind::COUNT],
+    dropped: AtomicUsize,
+}
+
+impl WinEventFilter {
+    pub const fn new() -> Self {
+        Self {
+            game_window: AtomicUsize::new(0),
+            game_pid: AtomicU32::new(0),
+            pending: [
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+            ],
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The game window found by the main loop, with its process if known.
+    pub fn set_game(&self, game: Option<(usize, Option<u32>)>) {
+        let (window, pid) = game.unwrap_or_default();
+        self.game_window.store(window, Ordering::SeqCst);
+        self.game_pid.store(pid.unwrap_or(0), Ordering::SeqCst);
+    }
+
+    /// Whether `hwnd` is the game window, so the hook knows when a process
+    /// lookup is worth it.
+    pub fn is_game_window(&self, hwnd: usize) -> bool {
+        hwnd != 0 && hwnd == self.game_window.load(Ordering::SeqCst)
+    }
+
+    fn is_relevant(&self, event: &WinEvent) -> bool {
+        if !event.is_top_level_window() {
+            return false;
+        }
+        match event.kind {
+            // Any window taking the focus can take it from the game
+            WinEventKind::Foreground => true,
+            // A handle owned by another process was reused after the game closed
+            WinEventKind::Destroy => {
+                let game_pid = self.game_pid.load(Ordering::SeqCst);
+                self.is_game_window(event.hwnd)
+                    && (game_pid == 0 || event.pid.is_none_or(|pid| pid == game_pid))
+            }
+            WinEventKind::LocationChange => self.is_game_window(event.hwnd),
+        }
+    }
+
+    /// Whether the hook should send the event. Everything else is counted as dropped.
+    pub fn accept(&self, event: &WinEvent) -> bool {
+        let accepted = self.is_relevant(event)
+            && !self.pending[event.kind.index()].swap(true, Ordering::SeqCst);
+        if !accepted {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        accepted
+    }
+
+    /// Called by the main loop before it looks at the windows, so events from
+    /// then on are sent again.
+    pub fn handled(&self, kind: WinEventKind) {
+        self.pending[kind.index()].store(false, Ordering::SeqCst);
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+pub static WIN_EVENTS: WinEventFilter = WinEventFilter::new();
+
+/// Holds off an action until events stop coming for `delay`, so dragging a
+/// window recomputes its props once it is let go rather than on every step.
+#[derive(Clone, Copy, Debug)]
+pub struct Debounce {
+    delay: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Debounce {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            deadline: None,
+        }
+    }
+
+    pub fn on_event(&mut self, now: Instant) {
+        self.deadline = Some(now + self.delay);
+    }
+
+    /// When the action is due, `None` if nothing is waiting.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Whether the action is due at `now`. It then counts as done.
+    pub fn fire(&mut self, now: Instant) -> bool {
+        match self.deadline {
+            Some(deadline) if deadline <= now => {
+                self.deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use WinEventKind::*;
+
+    const GAME: usize = 0x1234;
+    const GAME_PID: u32 = 4242;
+
+    fn event(kind: WinEventKind, hwnd: usize, id_object: i32, pid: Option<u32>) -> WinEvent {
+        WinEvent {
+            kind,
+            hwnd,
+            id_object,
+            id_child: CHILDID_SELF,
+            pid,
+        }
+    }
+
+    #[test]
+    fn keeps_top_level_windows_of_the_game() {
+        let filter = WinEventFilter::new();
+        filter.set_game(Some((GAME, Some(GAME_PID))));
+
+        // (event, accepted). OBJID_CARET is -8, OBJID_CURSOR is -9.
+        #[rustfmt::skip]
+        let table = [
+            (event(Foreground, 0x99, OBJID_WINDOW, Some(1)), true),
+            (event(Foreground, GAME, -8, Some(GAME_PID)), false),
+            (event(Destroy, GAME, OBJID_WINDOW, None), true),
+            (event(Destroy, GAME, OBJID_WINDOW, Some(GAME_PID)), true),
+            (event(Destroy, GAME, OBJID_WINDOW, Some(1)), false),
+            (event(Destroy, 0x77, OBJID_WINDOW, None), false),
+            (event(Destroy, 0x99, OBJID_WINDOW, None), false),
+            (event(Destroy, 0x99, OBJID_WINDOW, Some(1)), false),
+            (event(Destroy, GAME, -9, None), false),
+            (event(Destroy, 0, OBJID_WINDOW, None), false),
+            (event(LocationChange, GAME, OBJID_WINDOW, Some(GAME_PID)), true),
+            (event(LocationChange, GAME, -9, Some(GAME_PID)), false),
+            (event(LocationChange, 0x77, OBJID_WINDOW, Some(GAME_PID)), false),
+            (event(LocationChange, 0x99, OBJID_WINDOW, Some(1)), false),
+        ];
+        for (event, accepted) in table {
+            assert_eq!(filter.is_relevant(&event), accepted, "{event:?}");
+        }
+
+        let child = WinEvent {
+            id_child: 3,
+            ..event(Destroy, GAME, OBJID_WINDOW, None)
+        };
+        assert!(!filter.is_relevant(&child));
+    }
+
+    #[test]
+    fn game_events_are_ignored_without_a_game() {
+        let filter = WinEventFilter::new();
+        assert!(!filter.is_relevant(&event(Destroy, GAME, OBJID_WINDOW, None)));
+        assert!(!filter.is_relevant(&event(LocationChange, GAME, OBJID_WINDOW, None)));
+        assert!(filter.is_relevant(&event(Foreground, GAME, OBJID_WINDOW, None)));
+
+        assert!(!filter.is_game_window(GAME));
+        assert!(!filter.is_game_window(0));
+
+        filter.set_game(Some((GAME, None)));
+        assert!(filter.is_game_window(GAME));
+        assert!(!filter.is_game_window(0x99));
+        assert!(filter.is_relevant(&event(Destroy, GAME, OBJID_WINDOW, Some(1))));
+        filter.set_game(None);
+        assert!(!filter.is_relevant(&event(Destroy, GAME, OBJID_WINDOW, None)));
+    }
+
+    #[test]
+    fn coalesces_bursts_until_handled() {
+        let filter = WinEventFilter::new();
+        filter.set_game(Some((GAME, Some(GAME_PID))));
+        let focus = event(Foreground, 0x99, OBJID_WINDOW, None);
+        let destroy = event(Destroy, GAME, OBJID_WINDOW, None);
+
+        let sent = (0..5).filter(|_| filter.accept(&focus)).count();
+        assert_eq!(sent, 1);
+        // Each kind has its own slot
+        assert!(filter.accept(&destroy));
+
+        filter.handled(Foreground);
+        assert!(filter.accept(&focus));
+        assert!(!filter.accept(&destroy));
+
+        let tooltip = event(Destroy, 0x99, OBJID_WINDOW, None);
+        assert!(!filter.accept(&tooltip));
+        assert_eq!(filter.dropped(), 6);
+    }
+
+    #[test]
+    fn debounce_waits_for_the_last_event() {
+        const MS: Duration = Duration::from_millis(1);
+        let start = Instant::now();
+        let mut debounce = Debounce::new(200 * MS);
+        assert_eq!(debounce.deadline(), None);
+        assert!(!debounce.fire(start));
+
+        // A drag: an event every 16 ms for a second
+        for i in 0..60 {
+            debounce.on_event(start + i * 16 * MS);
+            assert!(!debounce.fire(start + i * 16 * MS));
+        }
+        let last = start + 59 * 16 * MS;
+        assert_eq!(debounce.deadline(), Some(last + 200 * MS));
+        assert!(!debounce.fire(last + 199 * MS));
+        assert!(debounce.fire(last + 200 * MS));
+        assert!(!debounce.fire(last + 400 * MS));
+        assert_eq!(debounce.deadline(), None);
+    }
+}
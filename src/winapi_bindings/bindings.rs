@@ -1,26 +1,42 @@
 use color_eyre::Result;
-use std::ptr::null_mut;
+use std::{io, ptr::null_mut};
 
 use winapi::{
     shared::{
-        minwindef::{FALSE, HINSTANCE, TRUE, WPARAM},
-        windef::{HDC, HHOOK, HWINEVENTHOOK, HWND, RECT},
+        minwindef::{BOOL, FALSE, HINSTANCE, LPARAM, TRUE, WPARAM},
+        windef::{
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, DPI_AWARENESS_PER_MONITOR_AWARE, HDC,
+            HGDIOBJ, HHOOK, HWINEVENTHOOK, HWND, POINT, RECT,
+        },
     },
     um::{
         consoleapi::SetConsoleCtrlHandler,
+        handleapi::CloseHandle,
+        processthreadsapi::{GetCurrentThreadId, OpenProcess},
+        winbase::QueryFullProcessImageNameW,
         wincon::PHANDLER_ROUTINE,
-        wingdi::{GetBValue, GetGValue, GetPixel, GetRValue, CLR_INVALID},
+        wingdi::{
+            BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetBValue,
+            GetDIBits, GetGValue, GetPixel, GetRValue, SelectObject, BITMAPINFO, BITMAPINFOHEADER,
+            BI_RGB, CLR_INVALID, DIB_RGB_COLORS, SRCCOPY,
+        },
+        winnt::PROCESS_QUERY_LIMITED_INFORMATION,
         winuser::{
-            DispatchMessageW, FindWindowW, GetDC, GetForegroundWindow, GetMessageW, GetWindowRect,
-            ReleaseDC, SendMessageW, SetCursorPos, SetWinEventHook, SetWindowsHookExW,
-            TranslateMessage, UnhookWinEvent, UnhookWindowsHookEx, HOOKPROC, LPMSG, VK_SPACE,
-            WH_KEYBOARD_LL, WINEVENTPROC, WINEVENT_OUTOFCONTEXT, WM_KEYDOWN, WM_LBUTTONDOWN,
-            WM_LBUTTONUP,
+            ClientToScreen, DispatchMessageW, EnumWindows, GetAwarenessFromDpiAwarenessContext,
+            GetClassNameW, GetClientRect, GetDC, GetDesktopWindow, GetDpiForWindow,
+            GetForegroundWindow, GetMessageW, GetThreadDpiAwarenessContext,
+            GetWindowDpiAwarenessContext, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId,
+            IsWindowVisible, PeekMessageW, PostThreadMessageW, ReleaseDC, SendMessageW,
+            SetCursorPos, SetProcessDpiAwarenessContext, SetWinEventHook, SetWindowsHookExW,
+            TranslateMessage, UnhookWinEvent, UnhookWindowsHookEx, HOOKPROC, LPMSG, PM_NOREMOVE,
+            VK_SPACE, WH_KEYBOARD_LL, WINEVENTPROC, WINEVENT_OUTOFCONTEXT, WM_KEYDOWN,
+            WM_LBUTTONDOWN, WM_LBUTTONUP, WM_QUIT, WM_USER,
         },
     },
 };
 
 use super::utils::{last_os_error, make_lparam};
+use crate::coords::Rect;
 
 pub fn set_win_event_hook(event: u32, handler: WINEVENTPROC) -> Result<HWINEVENTHOOK> {
     match unsafe {
@@ -46,22 +62,50 @@ pub fn set_window_hook_keyboard_ll(handler: HOOKPROC) -> Result<HHOOK> {
     }
 }
 
-pub fn get_global_message(msg: LPMSG) -> Result<()> {
+/// Returns `false` once `WM_QUIT` is received.
+pub fn get_global_message(msg: LPMSG) -> Result<bool> {
     match unsafe { GetMessageW(msg, null_mut(), 0, 0) } {
-        TRUE => Ok(()),
-        FALSE => Err(last_os_error()),
-        u => unreachable!(),
+        -1 => Err(last_os_error()),
+        FALSE => Ok(false),
+        _ => Ok(true),
     }
 }
 
-pub fn translate_message(msg: LPMSG) -> Result<()> {
-    match unsafe { TranslateMessage(msg) } {
-        TRUE => Ok(()),
+/// A thread only gets a message queue once it asks for messages. Peeking
+/// without removing anything is the usual way to force it.
+pub fn create_message_queue(msg: LPMSG) {
+    unsafe {
+        PeekMessageW(msg, null_mut(), WM_USER, WM_USER, PM_NOREMOVE);
+    }
+}
+
+pub fn get_current_thread_id() -> u32 {
+    unsafe { GetCurrentThreadId() }
+}
+
+/// Process owning the window, `None` once the window is gone.
+pub fn get_window_process_id(hwnd: HWND) -> Option<u32> {
+    let mut pid = 0;
+    match unsafe { GetWindowThreadProcessId(hwnd, &mut pid) } {
+        0 => None,
+        _ => Some(pid),
+    }
+}
+
+/// Makes `GetMessageW` on that thread return `false`.
+pub fn post_quit_to_thread(thread_id: u32) -> Result<()> {
+    match unsafe { PostThreadMessageW(thread_id, WM_QUIT, 0, 0) } {
         FALSE => Err(last_os_error()),
-        u => unreachable!(),
+        _ => Ok(()),
     }
 }
 
+/// Whether the message was a key that produced a character message. `false`
+/// is the usual answer for anything else, not a failure.
+pub fn translate_message(msg: LPMSG) -> bool {
+    unsafe { TranslateMessage(msg) != FALSE }
+}
+
 pub fn dispatch_message(msg: LPMSG) {
     unsafe {
         DispatchMessageW(msg);
@@ -114,6 +158,90 @@ pub fn get_color_pixel(hwnd: HWND, x: i32, y: i32) -> Result<(u8, u8, u8)> {
     Ok(rgb)
 }
 
+/// Copies `areas` of the window's client area into `bgra` as top-down 32-bit
+/// BGRA, one under the other against the left edge. Each area costs a `BitBlt`,
+/// converting them all takes a single `GetDIBits`.
+pub fn capture_window(hwnd: HWND, areas: &[Rect], bgra: &mut Vec<u8>) -> io::Result<()> {
+    let width = areas.iter().map(Rect::width).max().unwrap_or(0);
+    let height = areas.iter().map(Rect::height).sum();
+    let window_dc: HDC = unsafe { GetDC(hwnd) };
+    if window_dc.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let memory_dc = unsafe { CreateCompatibleDC(window_dc) };
+    if memory_dc.is_null() {
+        let err = io::Error::last_os_error();
+        unsafe { ReleaseDC(hwnd, window_dc) };
+        return Err(err);
+    }
+
+    let bitmap = unsafe { CreateCompatibleBitmap(window_dc, width, height) };
+    if bitmap.is_null() {
+        let err = io::Error::last_os_error();
+        unsafe {
+            DeleteDC(memory_dc);
+            ReleaseDC(hwnd, window_dc);
+        };
+        return Err(err);
+    }
+
+    let mut info: BITMAPINFO = unsafe { std::mem::zeroed() };
+    info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as u32;
+    info.bmiHeader.biWidth = width;
+    // A negative height asks for a top-down bitmap
+    info.bmiHeader.biHeight = -height;
+    info.bmiHeader.biPlanes = 1;
+    info.bmiHeader.biBitCount = 32;
+    info.bmiHeader.biCompression = BI_RGB;
+    bgra.resize((width * height * 4) as usize, 0);
+
+    let result = unsafe {
+        let previous = SelectObject(memory_dc, bitmap as HGDIOBJ);
+        let mut row = 0;
+        let copied = areas.iter().all(|area| {
+            let copied = BitBlt(
+                memory_dc,
+                0,
+                row,
+                area.width(),
+                area.height(),
+                window_dc,
+                area.left,
+                area.top,
+                SRCCOPY,
+            );
+            row += area.height();
+            copied != FALSE
+        });
+        // The bitmap must not be selected into a DC while GetDIBits reads it
+        SelectObject(memory_dc, previous);
+        if !copied {
+            Err(io::Error::last_os_error())
+        } else {
+            match GetDIBits(
+                memory_dc,
+                bitmap,
+                0,
+                height as u32,
+                bgra.as_mut_ptr().cast(),
+                &mut info,
+                DIB_RGB_COLORS,
+            ) {
+                0 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    };
+
+    unsafe {
+        DeleteObject(bitmap as HGDIOBJ);
+        DeleteDC(memory_dc);
+        ReleaseDC(hwnd, window_dc);
+    };
+    result
+}
+
 pub fn set_cursor_position(x: i32, y: i32) -> Result<()> {
     match unsafe { SetCursorPos(x, y) } {
         TRUE => Ok(()),
@@ -122,6 +250,10 @@ pub fn set_cursor_position(x: i32, y: i32) -> Result<()> {
     }
 }
 
+pub fn get_desktop_window() -> HWND {
+    unsafe { GetDesktopWindow() }
+}
+
 pub fn get_foreground_window() -> Option<HWND> {
     let hwnd = unsafe { GetForegroundWindow() };
     if hwnd.is_null() {
@@ -142,6 +274,58 @@ pub fn get_window_size(hwnd: HWND) -> Result<(i32, i32)> {
     }
 }
 
+pub fn get_window_rect(hwnd: HWND) -> Result<(i32, i32, i32, i32)> {
+    let mut rect: RECT = unsafe { std::mem::zeroed() };
+    if unsafe { GetWindowRect(hwnd, &mut rect) } != 0 {
+        Ok((rect.left, rect.top, rect.right, rect.bottom))
+    } else {
+        Err(last_os_error())
+    }
+}
+
+pub fn get_client_size(hwnd: HWND) -> io::Result<(i32, i32)> {
+    let mut rect: RECT = unsafe { std::mem::zeroed() };
+    if unsafe { GetClientRect(hwnd, &mut rect) } != 0 {
+        Ok((rect.right - rect.left, rect.bottom - rect.top))
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+pub fn client_to_screen(hwnd: HWND, x: i32, y: i32) -> Result<(i32, i32)> {
+    let mut point = POINT { x, y };
+    match unsafe { ClientToScreen(hwnd, &mut point) } {
+        FALSE => Err(last_os_error()),
+        _ => Ok((point.x, point.y)),
+    }
+}
+
+pub fn get_dpi_for_window(hwnd: HWND) -> Result<u32> {
+    match unsafe { GetDpiForWindow(hwnd) } {
+        0 => Err(last_os_error()),
+        dpi => Ok(dpi),
+    }
+}
+
+pub fn is_window_dpi_aware(hwnd: HWND) -> bool {
+    let awareness =
+        unsafe { GetAwarenessFromDpiAwarenessContext(GetWindowDpiAwarenessContext(hwnd)) };
+    awareness == DPI_AWARENESS_PER_MONITOR_AWARE
+}
+
+pub fn is_thread_dpi_aware() -> bool {
+    let awareness = unsafe { GetAwarenessFromDpiAwarenessContext(GetThreadDpiAwarenessContext()) };
+    awareness == DPI_AWARENESS_PER_MONITOR_AWARE
+}
+
+pub fn set_process_dpi_aware() -> Result<()> {
+    match unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) } {
+        TRUE => Ok(()),
+        FALSE => Err(last_os_error()),
+        _ => unreachable!(),
+    }
+}
+
 pub fn send_message_space(hwnd: HWND, key_press: bool) -> Result<()> {
     let flags = if key_press { 0x8000 } else { 0x0000 };
     let l_param = make_lparam(1, flags | VK_SPACE);
@@ -168,11 +352,49 @@ pub fn send_message_click(hwnd: HWND, key_press: bool, pos_x: i32, pos_y: i32) -
     }
 }
 
-pub fn find_window(w_name: &[u16], w_class_name: &[u16]) -> Result<HWND> {
-    let hwnd = unsafe { FindWindowW(w_class_name.as_ptr(), w_name.as_ptr()) };
-    if hwnd.is_null() {
-        Err(last_os_error())
-    } else {
-        Ok(hwnd)
+/// Visible top-level windows, topmost first.
+pub fn is_window_visible(hwnd: HWND) -> bool {
+    unsafe { IsWindowVisible(hwnd) != FALSE }
+}
+
+pub fn enum_visible_windows() -> Result<Vec<HWND>> {
+    unsafe extern "system" fn collect(hwnd: HWND, windows: LPARAM) -> BOOL {
+        let windows = &mut *(windows as *mut Vec<HWND>);
+        if IsWindowVisible(hwnd) != FALSE {
+            windows.push(hwnd);
+        }
+        TRUE
+    }
+
+    let mut windows: Vec<HWND> = Vec::new();
+    match unsafe { EnumWindows(Some(collect), &mut windows as *mut _ as LPARAM) } {
+        FALSE => Err(last_os_error()),
+        _ => Ok(windows),
+    }
+}
+
+pub fn get_window_title(hwnd: HWND) -> String {
+    let mut buffer = [0u16; 512];
+    let len = unsafe { GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+    String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+}
+
+pub fn get_class_name(hwnd: HWND) -> String {
+    let mut buffer = [0u16; 256];
+    let len = unsafe { GetClassNameW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+    String::from_utf16_lossy(&buffer[..len.max(0) as usize])
+}
+
+/// Full path of the process executable, `None` if the process can not be
+/// opened, as for one running elevated.
+pub fn get_process_image_path(pid: u32) -> Option<String> {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    if process.is_null() {
+        return None;
     }
+    let mut buffer = [0u16; 1024];
+    let mut len = buffer.len() as u32;
+    let result = unsafe { QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut len) };
+    unsafe { CloseHandle(process) };
+    (result != FALSE).then(|| String::from_utf16_lossy(&buffer[..len as usize]))
 }
@@ -1,23 +1,28 @@
 use super::bindings::{
-    set_win_event_hook, set_window_hook_keyboard_ll, unhook_win_event, unhook_windows_hook_ex,
+    get_window_process_id, set_win_event_hook, set_window_hook_keyboard_ll, unhook_win_event,
+    unhook_windows_hook_ex,
 };
 use crate::{
+    dispatch::{self, HOOK_ERRORS},
     global_states::{EventType, EVENT_LISTENER_CHANNEL},
+    hotkeys::{KeyCombo, KeyboardState},
+    win_events::{WinEvent, WinEventKind, WIN_EVENTS},
     winapi_bindings::bindings::set_console_ctrl_handler,
 };
-use color_eyre::{
-    eyre::{Context, ContextCompat},
-    Result,
-};
-use std::ptr::null_mut;
+use color_eyre::Result;
+use std::{ptr::null_mut, sync::Mutex};
 use winapi::{
     shared::{
         minwindef::{DWORD, LPARAM, LRESULT, UINT, WPARAM},
         windef::{HHOOK, HWINEVENTHOOK, HWND},
     },
-    um::winuser::{
-        CallNextHookEx, EVENT_OBJECT_DESTROY, EVENT_SYSTEM_FOREGROUND, HC_ACTION, KBDLLHOOKSTRUCT,
-        WM_KEYDOWN,
+    um::{
+        winnt::LONG,
+        winuser::{
+            CallNextHookEx, EVENT_OBJECT_DESTROY, EVENT_OBJECT_LOCATIONCHANGE,
+            EVENT_SYSTEM_FOREGROUND, HC_ACTION, KBDLLHOOKSTRUCT, WM_KEYDOWN, WM_KEYUP,
+            WM_SYSKEYDOWN, WM_SYSKEYUP,
+        },
     },
 };
 
@@ -36,13 +41,17 @@ impl ChangeForegroundWindow {
     unsafe extern "system" fn handler(
         _: HWINEVENTHOOK,
         _: DWORD,
-        _: HWND,
-        _: winapi::um::winnt::LONG,
-        _: winapi::um::winnt::LONG,
+        hwnd: HWND,
+        id_object: LONG,
+        id_child: LONG,
         _: DWORD,
         _: DWORD,
     ) {
-        send_event(EventType::ChangeForegroundWindow).unwrap();
+        dispatch((), |emit| {
+            if let Some(event) = win_event(WinEventKind::Foreground, hwnd, id_object, id_child) {
+                emit(EventType::ChangeForegroundWindow(event));
+            }
+        })
     }
 }
 
@@ -67,13 +76,17 @@ impl DestroyWindow {
     unsafe extern "system" fn handler(
         _: HWINEVENTHOOK,
         _: DWORD,
-        _: HWND,
-        _: winapi::um::winnt::LONG,
-        _: winapi::um::winnt::LONG,
+        hwnd: HWND,
+        id_object: LONG,
+        id_child: LONG,
         _: DWORD,
         _: DWORD,
     ) {
-        send_event(EventType::DestroyWindow).unwrap();
+        dispatch((), |emit| {
+            if let Some(event) = win_event(WinEventKind::Destroy, hwnd, id_object, id_child) {
+                emit(EventType::DestroyWindow(event));
+            }
+        })
     }
 }
 
@@ -83,6 +96,42 @@ impl Drop for DestroyWindow {
     }
 }
 
+pub struct LocationChange {
+    hook: HWINEVENTHOOK,
+}
+
+impl LocationChange {
+    const HOOK_TYPE: UINT = EVENT_OBJECT_LOCATIONCHANGE;
+
+    pub fn new() -> Result<Self> {
+        let hook = set_win_event_hook(Self::HOOK_TYPE, Some(Self::handler))?;
+        Ok(Self { hook })
+    }
+
+    unsafe extern "system" fn handler(
+        _: HWINEVENTHOOK,
+        _: DWORD,
+        hwnd: HWND,
+        id_object: LONG,
+        id_child: LONG,
+        _: DWORD,
+        _: DWORD,
+    ) {
+        dispatch((), |emit| {
+            if let Some(event) = win_event(WinEventKind::LocationChange, hwnd, id_object, id_child)
+            {
+                emit(EventType::LocationChange(event));
+            }
+        })
+    }
+}
+
+impl Drop for LocationChange {
+    fn drop(&mut self) {
+        let _ = unhook_win_event(self.hook);
+    }
+}
+
 pub struct ApplicationShutdown;
 
 impl ApplicationShutdown {
@@ -92,18 +141,27 @@ impl ApplicationShutdown {
     }
 
     unsafe extern "system" fn handler(_: u32) -> i32 {
-        send_event(EventType::Shutdown).unwrap();
-        1
+        // Not handling it lets Windows end the process, better than hanging
+        // around when the app never hears about it
+        dispatch(0, |emit| i32::from(emit(EventType::Shutdown)))
     }
 }
 
+/// Kept across hook calls. Only the listener thread calls the hook, so the lock
+/// is never contended.
+static KEYBOARD_STATE: Mutex<KeyboardState> = Mutex::new(KeyboardState::new());
+
 #[derive(Debug)]
 pub struct KeyboardEvent {
     hook: HHOOK,
 }
 
 impl KeyboardEvent {
-    pub fn new() -> Result<Self> {
+    /// `swallowed` key combinations are reported but not passed on to other windows.
+    pub fn new(swallowed: Vec<KeyCombo>) -> Result<Self> {
+        if let Ok(mut state) = KEYBOARD_STATE.lock() {
+            state.set_swallowed(swallowed);
+        }
         let hook = set_window_hook_keyboard_ll(Some(Self::handler))?;
         Ok(Self { hook })
     }
@@ -113,9 +171,28 @@ impl KeyboardEvent {
         w_param: WPARAM,
         l_param: LPARAM,
     ) -> LRESULT {
-        if code == HC_ACTION && w_param == WM_KEYDOWN as usize {
+        let swallow = dispatch(false, |emit| {
+            if code != HC_ACTION {
+                return false;
+            }
+            // Alt combinations arrive as WM_SYSKEY* instead of WM_KEY*
+            let pressed = match w_param as UINT {
+                WM_KEYDOWN | WM_SYSKEYDOWN => true,
+                WM_KEYUP | WM_SYSKEYUP => false,
+                _ => return false,
+            };
             let kbd_struct = &*(l_param as *const KBDLLHOOKSTRUCT);
-            send_event(EventType::KeyPress(kbd_struct.vkCode)).unwrap();
+            let (key_event, swallow) = match KEYBOARD_STATE.lock() {
+                Ok(mut state) => state.process(kbd_struct.vkCode, pressed),
+                Err(_) => (None, false),
+            };
+            if let Some(key_event) = key_event {
+                emit(EventType::Key(key_event));
+            }
+            swallow
+        });
+        if swallow {
+            return 1;
         }
 
         CallNextHookEx(null_mut(), code, w_param, l_param)
@@ -128,11 +205,23 @@ impl Drop for KeyboardEvent {
     }
 }
 
-fn send_event(event: EventType) -> Result<()> {
-    EVENT_LISTENER_CHANNEL
-        .get()
-        .wrap_err("event listener retrieval error")?
-        .clone()
-        .send(event)
-        .wrap_err("event sending error")
+/// The event if `WIN_EVENTS` lets it through.
+fn win_event(kind: WinEventKind, hwnd: HWND, id_object: LONG, id_child: LONG) -> Option<WinEvent> {
+    let mut event = WinEvent {
+        kind,
+        hwnd: hwnd as usize,
+        id_object,
+        id_child,
+        pid: None,
+    };
+    // Every other window on the system is dropped by its handle alone
+    if kind == WinEventKind::Destroy && WIN_EVENTS.is_game_window(event.hwnd) {
+        event.pid = get_window_process_id(hwnd);
+    }
+    WIN_EVENTS.accept(&event).then_some(event)
+}
+
+/// Every callback goes through here so nothing unwinds into Windows.
+fn dispatch<R>(fallback: R, callback: impl FnOnce(&dyn Fn(EventType) -> bool) -> R) -> R {
+    dispatch::guarded(&EVENT_LISTENER_CHANNEL, &HOOK_ERRORS, fallback, callback)
 }
@@ -1,29 +1,34 @@
-use super::bindings::{dispatch_message, get_global_message, translate_message};
+use super::bindings::{
+    create_message_queue, dispatch_message, get_global_message, translate_message,
+};
 use color_eyre::Result;
-use winapi::um::winuser::LPMSG;
+use winapi::um::winuser::MSG;
 
 pub struct MessageManager {
-    msg: LPMSG,
+    msg: MSG,
 }
 
 impl MessageManager {
+    /// Also makes sure the calling thread has a message queue, so messages
+    /// posted to it from now on are kept.
     pub fn new() -> Self {
-        MessageManager {
+        let mut manager = MessageManager {
             msg: unsafe { std::mem::zeroed() },
-        }
+        };
+        create_message_queue(&mut manager.msg);
+        manager
     }
 
-    pub fn get_message(&mut self) -> Result<()> {
-        get_global_message(self.msg)?;
-        Ok(())
+    /// Waits for the next message, `false` when the thread was asked to quit.
+    pub fn get_message(&mut self) -> Result<bool> {
+        get_global_message(&mut self.msg)
     }
 
-    pub fn translate_message(&mut self) -> Result<()> {
-        translate_message(self.msg)?;
-        Ok(())
+    pub fn translate_message(&mut self) {
+        translate_message(&mut self.msg);
     }
 
     pub fn dispatch_message(&mut self) {
-        dispatch_message(self.msg);
+        dispatch_message(&mut self.msg);
     }
 }
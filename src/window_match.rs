@@ -0,0 +1,255 @@
+use regex_lite::Regex;
+use serde::Deserialize;
+use std::{cell::OnceCell, fmt};
+
+/// What is known about a top-level window when looking for the game.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub hwnd: usize,
+    pub title: String,
+    pub class: String,
+    /// File name of the executable, `None` inside if the process could not be
+    /// opened. Only looked up once a matcher needs it, opening the process of
+    /// every window costs far more than comparing titles and classes.
+    exe: OnceCell<Option<String>>,
+}
+
+impl WindowInfo {
+    pub fn new(hwnd: usize, title: String, class: String) -> Self {
+        Self {
+            hwnd,
+            title,
+            class,
+            exe: OnceCell::new(),
+        }
+    }
+
+    /// A window whose executable is already known.
+    pub fn with_exe(self, exe: Option<String>) -> Self {
+        Self {
+            exe: OnceCell::from(exe),
+            ..self
+        }
+    }
+
+    fn exe(&self, lookup: &impl Fn(usize) -> Option<String>) -> Option<&str> {
+        self.exe.get_or_init(|| lookup(self.hwnd)).as_deref()
+    }
+}
+
+/// A regular expression the window title has to match somewhere.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TitlePattern(Regex);
+
+impl TitlePattern {
+    fn is_match(&self, title: &str) -> bool {
+        self.0.is_match(title)
+    }
+}
+
+impl TryFrom<String> for TitlePattern {
+    type Error = regex_lite::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Regex::new(&value).map(TitlePattern)
+    }
+}
+
+impl fmt::Debug for TitlePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TitlePattern({:?})", self.0.as_str())
+    }
+}
+
+impl PartialEq for TitlePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// One way of recognising the game window. Every field that is given has to
+/// match, a matcher with no fields at all matches nothing.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowMatcher {
+    /// Exact titles, any of them.
+    #[serde(default)]
+    pub title: Vec<String>,
+    pub title_regex: Option<TitlePattern>,
+    pub class: Option<String>,
+    /// Executable file names, any of them, ignoring case.
+    #[serde(default)]
+    pub exe: Vec<String>,
+}
+
+impl WindowMatcher {
+    fn is_empty(&self) -> bool {
+        self.title.is_empty()
+            && self.title_regex.is_none()
+            && self.class.is_none()
+            && self.exe.is_empty()
+    }
+
+    /// `lookup` finds the executable of a window, it is only called once the
+    /// title and class match.
+    pub fn matches(&self, window: &WindowInfo, lookup: &impl Fn(usize) -> Option<String>) -> bool {
+        let title = self.title.is_empty() || self.title.contains(&window.title);
+        let title_regex = || {
+            self.title_regex
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&window.title))
+        };
+        let class = || {
+            self.class
+                .as_ref()
+                .is_none_or(|class| *class == window.class)
+        };
+        let exe = || {
+            self.exe.is_empty()
+                || window.exe(lookup).is_some_and(|exe| {
+                    self.exe
+                        .iter()
+                        .any(|wanted| wanted.eq_ignore_ascii_case(exe))
+                })
+        };
+        !self.is_empty() && title && title_regex() && class() && exe()
+    }
+}
+
+/// The window the first matcher to match anything points at. Matchers come in
+/// order of preference, windows in the order Windows lists them, topmost first.
+pub fn find_match<'a>(
+    matchers: &[WindowMatcher],
+    windows: &'a [WindowInfo],
+    lookup: &impl Fn(usize) -> Option<String>,
+) -> Option<&'a WindowInfo> {
+    matchers.iter().find_map(|matcher| {
+        windows
+            .iter()
+            .find(|window| matcher.matches(window, lookup))
+    })
+}
+
+/// The file name at the end of a full executable path.
+pub fn exe_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(hwnd: usize, title: &str, class: &str, exe: Option<&str>) -> WindowInfo {
+        WindowInfo::new(hwnd, title.to_string(), class.to_string())
+            .with_exe(exe.map(str::to_string))
+    }
+
+    /// For windows whose executable is known already.
+    fn no_lookup(hwnd: usize) -> Option<String> {
+        panic!("Looked up the executable of window {hwnd}")
+    }
+
+    fn matcher(toml: &str) -> WindowMatcher {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn every_given_field_has_to_match() {
+        let genshin = matcher(
+            r#"
+            exe = ["GenshinImpact.exe", "YuanShen.exe"]
+            class = "UnityWndClass"
+            "#,
+        );
+
+        #[rustfmt::skip]
+        let table = [
+            (window(1, "Genshin Impact", "UnityWndClass", Some("GenshinImpact.exe")), true),
+            (window(2, "原神", "UnityWndClass", Some("YuanShen.exe")), true),
+            (window(3, "Renamed", "UnityWndClass", Some("yuanshen.EXE")), true),
+            (window(4, "Genshin Impact", "Chrome_WidgetWin_1", Some("GenshinImpact.exe")), false),
+            (window(5, "Genshin Impact", "UnityWndClass", Some("StarRail.exe")), false),
+            (window(6, "Genshin Impact", "UnityWndClass", None), false),
+        ];
+        for (window, expected) in table {
+            assert_eq!(genshin.matches(&window, &no_lookup), expected, "{window:?}");
+        }
+    }
+
+    #[test]
+    fn matches_titles_exactly_or_by_pattern() {
+        let exact = matcher(r#"title = ["Genshin Impact", "原神"]"#);
+        assert!(exact.matches(&window(1, "原神", "", None), &no_lookup));
+        assert!(!exact.matches(
+            &window(1, "Genshin Impact Wiki - Browser", "", None),
+            &no_lookup
+        ));
+
+        let pattern = matcher(r#"title_regex = "^(云·)?原神$""#);
+        assert!(pattern.matches(&window(1, "云·原神", "", None), &no_lookup));
+        assert!(pattern.matches(&window(1, "原神", "", None), &no_lookup));
+        assert!(!pattern.matches(&window(1, "原神 - 攻略", "", None), &no_lookup));
+
+        assert!(toml::from_str::<WindowMatcher>(r#"title_regex = "(unclosed""#).is_err());
+    }
+
+    #[test]
+    fn an_empty_matcher_matches_nothing() {
+        assert!(!WindowMatcher::default().matches(&window(1, "", "", None), &no_lookup));
+    }
+
+    #[test]
+    fn prefers_earlier_matchers() {
+        let matchers = [
+            matcher(r#"exe = ["GenshinImpact.exe"]"#),
+            matcher(r#"title = ["Genshin Impact"]"#),
+        ];
+        let windows = [
+            window(1, "Genshin Impact", "Notepad", Some("notepad.exe")),
+            window(2, "Genshin", "UnityWndClass", Some("GenshinImpact.exe")),
+        ];
+        assert_eq!(
+            find_match(&matchers, &windows, &no_lookup).map(|w| w.hwnd),
+            Some(2)
+        );
+        assert_eq!(
+            find_match(&matchers[1..], &windows, &no_lookup).map(|w| w.hwnd),
+            Some(1)
+        );
+        assert_eq!(find_match(&matchers, &windows[..0], &no_lookup), None);
+    }
+
+    #[test]
+    fn opens_only_processes_of_matching_windows() {
+        let matchers = [
+            matcher("exe = [\"GenshinImpact.exe\"]\nclass = \"UnityWndClass\""),
+            matcher("exe = [\"YuanShen.exe\"]\nclass = \"UnityWndClass\""),
+        ];
+        let windows = [
+            WindowInfo::new(1, "Inbox".to_string(), "Chrome_WidgetWin_1".to_string()),
+            WindowInfo::new(2, "Some game".to_string(), "UnityWndClass".to_string()),
+            WindowInfo::new(3, "原神".to_string(), "UnityWndClass".to_string()),
+        ];
+        let looked_up = std::cell::RefCell::new(Vec::new());
+        let lookup = |hwnd| {
+            looked_up.borrow_mut().push(hwnd);
+            (hwnd == 3).then(|| "YuanShen.exe".to_string())
+        };
+        assert_eq!(
+            find_match(&matchers, &windows, &lookup).map(|w| w.hwnd),
+            Some(3)
+        );
+        // Once per window, however many matchers ask
+        assert_eq!(*looked_up.borrow(), [2, 3]);
+    }
+
+    #[test]
+    fn takes_the_file_name_of_a_path() {
+        assert_eq!(
+            exe_name(r"C:\Program Files\Genshin Impact\Genshin Impact Game\GenshinImpact.exe"),
+            "GenshinImpact.exe"
+        );
+        assert_eq!(exe_name("YuanShen.exe"), "YuanShen.exe");
+    }
+}
@@ -0,0 +1,367 @@
+use crate::genshin::DialogAction;
+use color_eyre::Result;
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// What the sender thread works on, handed over by the main loop.
+///
+/// The sender blocks on it while there is nothing to do and wakes up as soon
+/// as the target changes, instead of polling a flag.
+#[derive(Debug, Default)]
+pub struct TargetSlot<T> {
+    slot: Mutex<Slot<T>>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    target: Option<T>,
+    /// Bumped on every change so the sender notices even if the new target
+    /// looks like the old one.
+    generation: u64,
+    closed: bool,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self {
+            target: None,
+            generation: 0,
+            closed: false,
+        }
+    }
+}
+
+impl<T: Clone> TargetSlot<T> {
+    pub fn new() -> Self {
+        Self {
+            slot: Mutex::new(Slot::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// A poisoned lock only means the other side panicked, the slot itself is
+    /// always in a consistent state.
+    fn lock(&self) -> MutexGuard<'_, Slot<T>> {
+        self.slot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set(&self, target: Option<T>) {
+        let mut slot = self.lock();
+        slot.target = target;
+        slot.generation += 1;
+        self.changed.notify_all();
+    }
+
+    /// Makes the sender return. Later targets are ignored.
+    pub fn close(&self) {
+        let mut slot = self.lock();
+        slot.closed = true;
+        slot.target = None;
+        slot.generation += 1;
+        self.changed.notify_all();
+    }
+
+    /// Sleeps for `timeout` unless the slot gets closed first. Returns whether it did.
+    pub fn wait_closed(&self, timeout: Duration) -> bool {
+        let (slot, _) = self
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |slot| !slot.closed)
+            .unwrap_or_else(PoisonError::into_inner);
+        slot.closed
+    }
+
+    /// Blocks until there is a target, `None` once the slot is closed.
+    fn wait_for_target(&self) -> Option<(T, u64)> {
+        let slot = self
+            .changed
+            .wait_while(self.lock(), |slot| !slot.closed && slot.target.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+        let target = slot.target.clone()?;
+        Some((target, slot.generation))
+    }
+
+    /// Sleeps for `timeout` unless the target changes first. Returns whether it did.
+    fn wait_for_change(&self, generation: u64, timeout: Duration) -> bool {
+        let (slot, _) = self
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |slot| slot.generation == generation)
+            .unwrap_or_else(PoisonError::into_inner);
+        slot.generation != generation
+    }
+
+    fn has_changed(&self, generation: u64) -> bool {
+        self.lock().generation != generation
+    }
+}
+
+/// The sender loop: calls `step` on the current target over and over, waiting
+/// `poll_interval` between frames with nothing to do, and blocks while there
+/// is no target. Returns when the slot is closed.
+pub fn run<T: Clone>(
+    slot: &TargetSlot<T>,
+    poll_interval: Duration,
+    mut step: impl FnMut(&T) -> Result<DialogAction>,
+) -> Result<()> {
+    while let Some((target, generation)) = slot.wait_for_target() {
+        loop {
+            let changed = match step(&target)? {
+                DialogAction::Wait => slot.wait_for_change(generation, poll_interval),
+                DialogAction::PressSpace | DialogAction::ClickOption => {
+                    slot.has_changed(generation)
+                }
+            };
+            if changed {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        genshin::{next_action, WindowProps},
+        pixel_source::FrameBuffer,
+        profile::GameProfile,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc::{channel, Receiver, Sender},
+            Arc,
+        },
+        thread::{self, JoinHandle},
+        time::Instant,
+    };
+
+    const LONG: Duration = Duration::from_secs(10);
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn blocks_until_a_target_is_set() {
+        let slot = Arc::new(TargetSlot::new());
+        let steps = Arc::new(AtomicUsize::new(0));
+        let (seen_sender, seen) = channel();
+
+        let worker = {
+            let (slot, steps) = (slot.clone(), steps.clone());
+            thread::spawn(move || {
+                run(&slot, LONG, |target: &u32| {
+                    steps.fetch_add(1, Ordering::SeqCst);
+                    seen_sender.send(*target).unwrap();
+                    Ok(DialogAction::Wait)
+                })
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(steps.load(Ordering::SeqCst), 0);
+
+        slot.set(Some(1));
+        assert_eq!(seen.recv_timeout(TIMEOUT).unwrap(), 1);
+        // A new target cuts the wait short
+        slot.set(Some(2));
+        assert_eq!(seen.recv_timeout(TIMEOUT).unwrap(), 2);
+
+        slot.set(None);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(steps.load(Ordering::SeqCst), 2);
+
+        slot.close();
+        worker.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn keeps_going_without_waiting_after_an_action() {
+        let slot = TargetSlot::new();
+        slot.set(Some(()));
+        let mut actions = [
+            DialogAction::PressSpace,
+            DialogAction::ClickOption,
+            DialogAction::PressSpace,
+        ]
+        .into_iter();
+
+        let started = Instant::now();
+        run(&slot, LONG, |_| match actions.next() {
+            Some(action) => Ok(action),
+            None => {
+                slot.close();
+                Ok(DialogAction::Wait)
+            }
+        })
+        .unwrap();
+        assert!(started.elapsed() < TIMEOUT);
+    }
+
+    #[test]
+    fn step_errors_end_the_worker() {
+        let slot = TargetSlot::new();
+        slot.set(Some(()));
+        let result = run(&slot, LONG, |_| {
+            Err(color_eyre::eyre::eyre!("capture failed"))
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn closing_cuts_a_wait_short() {
+        let slot = Arc::new(TargetSlot::<()>::new());
+        assert!(!slot.wait_closed(Duration::from_millis(10)));
+
+        let closer = {
+            let slot = slot.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                slot.close();
+            })
+        };
+        let started = Instant::now();
+        assert!(slot.wait_closed(LONG));
+        assert!(started.elapsed() < TIMEOUT);
+        closer.join().unwrap();
+    }
+
+    /// CPU time the calling thread has used, from the Linux scheduler statistics.
+    /// std has no per-thread CPU clock, other systems report nothing.
+    fn thread_cpu_time() -> Option<Duration> {
+        let stat = std::fs::read_to_string("/proc/thread-self/schedstat").ok()?;
+        let nanos = stat.split_whitespace().next()?.parse().ok()?;
+        Some(Duration::from_nanos(nanos))
+    }
+
+    struct Screen {
+        frame: FrameBuffer,
+        props: WindowProps,
+    }
+
+    /// Runs the real detection on a frame without any dialogue, so every step
+    /// ends in a wait, and reports when it ran.
+    fn detect(screen: &Screen, woke: &Sender<Instant>) -> Result<DialogAction> {
+        let _ = woke.send(Instant::now());
+        next_action(&screen.frame, &screen.props)
+    }
+
+    /// The loop this module replaced: check the target, sleep 50 ms, repeat.
+    fn spawn_polling(
+        target: Arc<Mutex<Option<Arc<Screen>>>>,
+        stop: Arc<AtomicBool>,
+        woke: Sender<Instant>,
+    ) -> JoinHandle<Option<Duration>> {
+        thread::spawn(move || {
+            let start = thread_cpu_time();
+            while !stop.load(Ordering::SeqCst) {
+                let current = target.lock().unwrap().clone();
+                if let Some(screen) = current {
+                    detect(&screen, &woke).unwrap();
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            Some(thread_cpu_time()? - start?)
+        })
+    }
+
+    fn spawn_event_driven(
+        slot: Arc<TargetSlot<Arc<Screen>>>,
+        woke: Sender<Instant>,
+    ) -> JoinHandle<Option<Duration>> {
+        thread::spawn(move || {
+            let start = thread_cpu_time();
+            run(&slot, Duration::from_millis(50), |screen| {
+                detect(screen, &woke)
+            })
+            .unwrap();
+            Some(thread_cpu_time()? - start?)
+        })
+    }
+
+    const IDLE: Duration = Duration::from_secs(2);
+    const WAKE_UPS: usize = 20;
+
+    /// Median and worst time from handing over a target to the first detection.
+    fn wake_latency(
+        woke: &Receiver<Instant>,
+        arm: impl Fn(),
+        disarm: impl Fn(),
+    ) -> (Duration, Duration) {
+        let mut latencies = Vec::new();
+        for i in 0..WAKE_UPS {
+            while woke.try_recv().is_ok() {}
+            let armed_at = Instant::now();
+            arm();
+            latencies.push(woke.recv_timeout(TIMEOUT).unwrap() - armed_at);
+            disarm();
+            // Spread the arms over the polling period
+            thread::sleep(Duration::from_millis(20 + (i as u64 * 17) % 50));
+        }
+        latencies.sort();
+        (latencies[WAKE_UPS / 2], latencies[WAKE_UPS - 1])
+    }
+
+    /// Compares the sender against the 50 ms polling loop it replaced, both
+    /// running the real detection on a fake frame. On a Linux dev box:
+    ///
+    /// ```text
+    /// sender             idle CPU  median wake   worst wake
+    /// condvar             67.72µs      26.50µs      74.46µs
+    /// 50 ms polling        1.52ms      26.99ms      46.14ms
+    /// ```
+    ///
+    /// `cargo test --release -- --ignored --nocapture bench_idle_cpu_and_wake_latency`
+    #[test]
+    #[ignore]
+    fn bench_idle_cpu_and_wake_latency() {
+        let frame = FrameBuffer::new(1920, 1080, (40, 60, 80));
+        let props = WindowProps::new(&frame, &GameProfile::default()).unwrap();
+        let screen = Arc::new(Screen { frame, props });
+        let (woke_sender, woke) = channel();
+
+        let slot = Arc::new(TargetSlot::new());
+        let idle = spawn_event_driven(slot.clone(), woke_sender.clone());
+        thread::sleep(IDLE);
+        slot.close();
+        let event_driven_idle = idle.join().unwrap();
+
+        let slot = Arc::new(TargetSlot::new());
+        let worker = spawn_event_driven(slot.clone(), woke_sender.clone());
+        let event_driven_latency =
+            wake_latency(&woke, || slot.set(Some(screen.clone())), || slot.set(None));
+        slot.close();
+        worker.join().unwrap();
+
+        let target = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let idle = spawn_polling(target.clone(), stop.clone(), woke_sender.clone());
+        thread::sleep(IDLE);
+        stop.store(true, Ordering::SeqCst);
+        let polling_idle = idle.join().unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = spawn_polling(target.clone(), stop.clone(), woke_sender);
+        let polling_latency = wake_latency(
+            &woke,
+            || *target.lock().unwrap() = Some(screen.clone()),
+            || *target.lock().unwrap() = None,
+        );
+        stop.store(true, Ordering::SeqCst);
+        worker.join().unwrap();
+
+        println!(
+            "{:<14} {:>12} {:>12} {:>12}",
+            "sender", "idle CPU", "median wake", "worst wake"
+        );
+        for (name, idle_cpu, (median, worst)) in [
+            ("condvar", event_driven_idle, event_driven_latency),
+            ("50 ms polling", polling_idle, polling_latency),
+        ] {
+            let idle_cpu = idle_cpu.map_or("n/a".to_string(), |cpu| format!("{cpu:.2?}"));
+            println!("{name:<14} {idle_cpu:>12} {median:>12.2?} {worst:>12.2?}");
+        }
+        println!("Idle CPU over {IDLE:?}, wake-up over {WAKE_UPS} arms");
+    }
+}